@@ -0,0 +1,191 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Ident, Span, TokenStream};
+use syn::{Block, FnArg, ItemFn, Pat, ReturnType, Type};
+
+/// The type that the parameter `name` of `func` refers to, if it is a shared
+/// reference like `&str`, `&[T]` or `&Book`.
+///
+/// The follow-up inputs of such a parameter are transformed as owned values,
+/// which each run borrows again.
+pub(crate) fn referent(func: &ItemFn, name: &str) -> Option<Type> {
+    func.sig.inputs.iter().find_map(|input| match input {
+        FnArg::Typed(pat_type) => match (&*pat_type.pat, &*pat_type.ty) {
+            (Pat::Ident(pat), Type::Reference(ty))
+                if pat.ident == name && ty.mutability.is_none() =>
+            {
+                Some((*ty.elem).clone())
+            }
+            _ => None,
+        },
+        FnArg::Receiver(_) => None,
+    })
+}
+
+/// The name of the owned type of a referent, as it is used to pick the
+/// transformation.
+pub(crate) fn owned_type(ident_type: &str) -> String {
+    if ident_type == "str" {
+        String::from("String")
+    } else if ident_type.starts_with('[') {
+        String::from("Vec")
+    } else {
+        String::from(ident_type)
+    }
+}
+
+/// Bind `owned` to an owned copy of the borrowed `source`.
+pub(crate) fn to_owned(source: &Ident, owned: &Ident) -> TokenStream {
+    quote::quote! {
+        let #owned = ::std::borrow::ToOwned::to_owned(#source);
+    }
+}
+
+/// Borrow the owned follow-up input `target` as the type the parameter
+/// refers to, right before the run that uses it.
+pub(crate) fn reborrow(target: &Ident, referent: &Type) -> TokenStream {
+    match referent {
+        // the type can't be written down, the reference is coerced instead
+        Type::ImplTrait(_) => quote::quote! {
+            let #target = &#target;
+        },
+        referent => quote::quote! {
+            let #target: &#referent = ::std::borrow::Borrow::borrow(&#target);
+        },
+    }
+}
+
+/// How the runs borrow their clone of the parameter `name` of `func`, if it
+/// is a receiver taken by reference or a `&mut` parameter.
+///
+/// The clone is owned, so a body that returns the parameter, like a builder
+/// returning `self`, only type checks on a reference to it.
+pub(crate) fn clone_borrow(func: &ItemFn, name: &str) -> Option<TokenStream> {
+    func.sig.inputs.iter().find_map(|input| match input {
+        FnArg::Receiver(receiver) if name == "self" => {
+            receiver.reference.as_ref()?;
+            let mutability = receiver.mutability;
+
+            Some(quote::quote!(&#mutability))
+        }
+        FnArg::Typed(pat_type) => match (&*pat_type.pat, &*pat_type.ty) {
+            (Pat::Ident(pat), Type::Reference(ty))
+                if pat.ident == name && ty.mutability.is_some() =>
+            {
+                Some(quote::quote!(&mut))
+            }
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Rebind the clone `target` as a reference for the run that uses it.
+pub(crate) fn borrow_clone(target: &Ident, by: &TokenStream) -> TokenStream {
+    quote::quote! {
+        let #target = #by #target;
+    }
+}
+
+/// The owned value of the result `ret` of a run, which is a clone unless the
+/// function returns a reference.
+pub(crate) fn owned_result(func: &ItemFn, ret: &Ident) -> TokenStream {
+    match &func.sig.output {
+        ReturnType::Type(_, ty) if matches!(**ty, Type::Reference(_)) => {
+            quote::quote!(::std::borrow::ToOwned::to_owned(&*#ret))
+        }
+        _ => quote::quote!(#ret.clone()),
+    }
+}
+
+/// Replace the result `ret` of a follow-up run that returns a reference by
+/// a reference to an owned snapshot, so it no longer borrows the inputs of
+/// that run.
+pub(crate) fn snapshot(func: &ItemFn, ret: &Ident) -> TokenStream {
+    match &func.sig.output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Reference(ty) => {
+                let mutability = ty.mutability;
+
+                quote::quote! {
+                    let #ret = &#mutability ::std::borrow::ToOwned::to_owned(&*#ret);
+                }
+            }
+            _ => TokenStream::new(),
+        },
+        ReturnType::Default => TokenStream::new(),
+    }
+}
+
+/// Whether `func` returns a reference.
+pub(crate) fn returns_reference(func: &ItemFn) -> bool {
+    matches!(
+        &func.sig.output,
+        ReturnType::Type(_, ty) if matches!(**ty, Type::Reference(_))
+    )
+}
+
+/// The parameters of `func` that are mutable references, `self` included.
+///
+/// The source run borrows them through a reborrow that its closure takes
+/// by value, so the reference it returns may outlive the closure.
+pub(crate) fn unique_references(func: &ItemFn) -> Vec<Ident> {
+    func.sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Receiver(receiver)
+                if receiver.reference.is_some()
+                    && receiver.mutability.is_some() =>
+            {
+                Some(Ident::new("self", Span::call_site()))
+            }
+            FnArg::Typed(pat_type) => match (&*pat_type.pat, &*pat_type.ty) {
+                (Pat::Ident(pat), Type::Reference(ty))
+                    if ty.mutability.is_some() =>
+                {
+                    Some(pat.ident.clone())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// The name of the reborrow of the mutable reference `name`.
+pub(crate) fn reborrow_name(name: &Ident) -> Ident {
+    Ident::new(&format!("__contract_borrow_{}", name), Span::call_site())
+}
+
+/// Bind `ret` to the result of a run of `block` that returns a reference.
+///
+/// The closure is called as a `FnOnce`, so the reference it returns may
+/// borrow from what it captures. `references` are reborrowed before and moved
+/// into the closure, `block` uses them by the names of [`reborrow_name`].
+pub(crate) fn run_once(
+    ret: &Ident,
+    ret_ty: &TokenStream,
+    block: &Block,
+    references: &[Ident],
+) -> TokenStream {
+    let reborrows = references.iter().map(reborrow_name).collect::<Vec<_>>();
+    let stmts = &block.stmts;
+
+    quote::quote! {
+        #(let #reborrows = &mut *#references;)*
+
+        let #ret = {
+            fn __contract_once<R>(run: impl FnOnce() -> R) -> R {
+                run()
+            }
+
+            __contract_once(|| #ret_ty {
+                #(let #reborrows = #reborrows;)*
+                #(#stmts)*
+            })
+        };
+    }
+}
@@ -0,0 +1,331 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Ident, TokenStream};
+use quote::ToTokens;
+use syn::{punctuated::Punctuated, Expr, Token};
+
+use crate::implementation::{text, Contract, ContractType};
+
+/// Name of the argument that lists the transformations of the input.
+const TRANSFORM: &str = "transform";
+
+/// Name of the argument that compares the output of every step of a chain
+/// with the output of the step before, as in `compare = steps`.
+const COMPARE: &str = "compare";
+
+/// One transformation of a chain, like `shift(1)` or `reverse`.
+pub(crate) struct Step {
+    name: Ident,
+    args: Punctuated<Expr, Token![,]>,
+}
+
+/// The transformations of `transform = [shift(1), reverse]`, if the relation
+/// has them.
+pub(crate) fn steps(
+    contract: &Contract,
+) -> Option<Result<Vec<Step>, syn::Error>> {
+    let transform = contract.option(TRANSFORM)?;
+
+    if replaced(contract.ty).is_none() {
+        let msg = format!(
+            "`{}` does not work with #[{}]",
+            TRANSFORM,
+            contract.ty.message_name()
+        );
+        return Some(Err(syn::Error::new_spanned(transform, msg)));
+    }
+
+    let elems = match transform {
+        Expr::Array(array) => &array.elems,
+        _ => {
+            let msg = "`transform` needs a list like `[shift(1), reverse]`";
+            return Some(Err(syn::Error::new_spanned(transform, msg)));
+        }
+    };
+
+    let steps = elems.iter().map(step).collect::<Result<Vec<_>, _>>();
+
+    // a relation of a chain that is compared step by step only applies the
+    // steps up to its own
+    Some(steps.map(|mut steps| {
+        if let Some(step) = contract.step {
+            steps.truncate(step + 1);
+        }
+        steps
+    }))
+}
+
+/// Turn a relation with `compare = steps` into one relation per step of its
+/// chain.
+///
+/// The relation of a step transforms the input by the steps up to its own
+/// and compares its output with the one of the relation before it, the
+/// first one with the output of the original call. They are kept next to
+/// each other, so the relation before is the previous contract.
+pub(crate) fn split(contract: Contract) -> Result<Vec<Contract>, syn::Error> {
+    let compare = match contract.option(COMPARE) {
+        Some(compare) => compare,
+        None => return Ok(vec![contract]),
+    };
+
+    if !matches!(compare, Expr::Path(path) if path.path.is_ident("steps")) {
+        let msg = "`compare` only takes `steps`";
+        return Err(syn::Error::new_spanned(compare, msg));
+    }
+
+    if !compares_steps(contract.ty) {
+        let msg = format!(
+            "`{}` does not work with #[{}]",
+            COMPARE,
+            contract.ty.message_name()
+        );
+        return Err(syn::Error::new_spanned(compare, msg));
+    }
+
+    // the output of the step before only exists in the block of its guard
+    if let Some(guard) = contract.option("when") {
+        let msg = "`compare = steps` does not work with `when`";
+        return Err(syn::Error::new_spanned(guard, msg));
+    }
+
+    let count = match steps(&contract) {
+        Some(Ok(steps)) => steps.len(),
+        Some(Err(err)) => return Err(err),
+        None => {
+            let msg = "`compare = steps` needs a chain like \
+                       `transform = [shift(1), reverse]`";
+            return Err(syn::Error::new_spanned(compare, msg));
+        }
+    };
+
+    Ok((0..count)
+        .map(|step| Contract {
+            step: Some(step),
+            ..contract.clone()
+        })
+        .collect())
+}
+
+/// Whether the relation has a single follow-up run, whose output can be
+/// compared with the output of the step before.
+fn compares_steps(ty: ContractType) -> bool {
+    matches!(
+        ty,
+        ContractType::Periodicity
+            | ContractType::AddNotEqual
+            | ContractType::DimensionTrans
+            | ContractType::Monotonicity
+            | ContractType::Symmetry
+            | ContractType::Inclusion
+    )
+}
+
+fn step(expr: &Expr) -> Result<Step, syn::Error> {
+    let (path, args) = match expr {
+        Expr::Path(path) => (path, Punctuated::new()),
+        Expr::Call(call) => match &*call.func {
+            Expr::Path(path) => (path, call.args.clone()),
+            _ => return Err(step_error(expr)),
+        },
+        _ => return Err(step_error(expr)),
+    };
+
+    match path.path.get_ident() {
+        Some(name) => Ok(Step {
+            name: name.clone(),
+            args,
+        }),
+        None => Err(step_error(expr)),
+    }
+}
+
+fn step_error(expr: &Expr) -> syn::Error {
+    let msg = "a transformation is a name like `reverse` or a call like \
+               `shift(1)`";
+    syn::Error::new_spanned(expr, msg)
+}
+
+/// The number of positional arguments that a chain replaces in a relation,
+/// if the relation can have one.
+fn replaced(ty: ContractType) -> Option<usize> {
+    match ty {
+        ContractType::Inclusion => Some(1),
+        ContractType::Periodicity
+        | ContractType::AddNotEqual
+        | ContractType::DimensionTrans
+        | ContractType::Monotonicity
+        | ContractType::Symmetry
+        | ContractType::Mapping => Some(2),
+        _ => None,
+    }
+}
+
+/// Leave room for the operator and the modifier that a chain replaces, so
+/// the other positional arguments keep their places.
+///
+/// Relations that can't have a chain are left as they are, [`steps`]
+/// reports the error for them.
+pub(crate) fn make_room(contract: &mut Contract) {
+    if contract.option(TRANSFORM).is_none() || contract.streams.is_empty() {
+        return;
+    }
+
+    let replaced = match replaced(contract.ty) {
+        Some(replaced) => replaced,
+        None => return,
+    };
+
+    for _ in 0..replaced {
+        contract.streams.insert(1, TokenStream::new());
+    }
+}
+
+/// Bind `target` to a clone of `source` that every step of the chain changes
+/// in turn.
+///
+/// `shift(n)` and `scale(n)` add and multiply, `reverse` and `rotate(n)`
+/// reorder elements or characters, and the text transformations change
+/// `&str` and `String` inputs. Any other step is a method call that changes
+/// the clone in place, like `swap(0, 1)`.
+pub(crate) fn follow_up(
+    ident_type: &str,
+    source: &Ident,
+    target: &Ident,
+    steps: &[Step],
+    mut_def: &TokenStream,
+) -> TokenStream {
+    let is_text = ident_type == "String";
+
+    let changes = steps.iter().map(|step| {
+        let Step { name, args } = step;
+
+        match name.to_string().as_str() {
+            "shift" => quote::quote! {
+                #target = ::std::ops::Add::add(#target, #args);
+            },
+            "scale" => quote::quote! {
+                #target = ::std::ops::Mul::mul(#target, #args);
+            },
+            "reverse" if is_text => quote::quote! {
+                #target = #target.chars().rev().collect();
+            },
+            "reverse" => quote::quote! {
+                #target.reverse();
+            },
+            "rotate" if is_text => quote::quote! {
+                #target = {
+                    let mut chars: ::std::vec::Vec<char> =
+                        #target.chars().collect();
+                    let len = chars.len().max(1);
+                    chars.rotate_left((#args) % len);
+                    chars.into_iter().collect()
+                };
+            },
+            "rotate" => quote::quote! {
+                let len = #target.len().max(1);
+                #target.rotate_left((#args) % len);
+            },
+            name if is_text && text::TRANSFORMS.contains(&name) => {
+                let modi = if args.len() > 1 {
+                    quote::quote!((#args))
+                } else {
+                    args.to_token_stream()
+                };
+                let changed =
+                    text::owned(&step.name, &target.to_token_stream(), &modi);
+
+                quote::quote! {
+                    #target = #changed;
+                }
+            }
+            _ => quote::quote! {
+                #target.#name(#args);
+            },
+        }
+    });
+
+    quote::quote! {
+        #[allow(unused_mut)]
+        let mut #target = #source.clone();
+        #(#changes)*
+        let #mut_def #target = #target;
+    }
+}
+
+/// The input of the follow-up run, written as `value.shift(1).reverse()`.
+pub(crate) fn describe(contract: &Contract, value: &str) -> Option<String> {
+    let steps = steps(contract)?.ok()?;
+
+    Some(format!("{}{}", value, calls(&steps)))
+}
+
+/// The input of the run that the follow-up run is compared with, if it is
+/// the one of the step before instead of the original input.
+pub(crate) fn source(contract: &Contract, value: &str) -> Option<String> {
+    let step = contract.step.filter(|step| *step > 0)?;
+    let steps = steps(contract)?.ok()?;
+
+    Some(format!("{}{}", value, calls(&steps[..step])))
+}
+
+fn calls(steps: &[Step]) -> String {
+    steps
+        .iter()
+        .map(|step| {
+            let args = step.args.to_token_stream().to_string();
+            format!(".{}({})", step.name, args)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::implementation::ContractMode;
+
+    #[test]
+    fn unsupported_relations_report_the_transform() {
+        let mut contract = Contract::from_toks(
+            ContractType::Homomorphism,
+            ContractMode::Test,
+            quote::quote!(x, +, 1, +, transform = [shift(1)]),
+        );
+        make_room(&mut contract);
+
+        let err = match steps(&contract) {
+            Some(Err(err)) => err,
+            _ => panic!("expected an error for the transform"),
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "`transform` does not work with #[homomorphism]"
+        );
+        assert_eq!(contract.streams.len(), 4);
+    }
+
+    #[test]
+    fn steps_are_split_into_relations() {
+        let mut contract = Contract::from_toks(
+            ContractType::Periodicity,
+            ContractMode::Test,
+            quote::quote!(xs, transform = [reverse, push(1)], compare = steps),
+        );
+        make_room(&mut contract);
+
+        let split = match split(contract) {
+            Ok(split) => split,
+            Err(err) => panic!("unexpected error: {}", err),
+        };
+        let steps = split
+            .iter()
+            .map(|contract| describe(contract, "xs").unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(steps, vec!["xs.reverse()", "xs.reverse().push(1)"]);
+        assert_eq!(source(&split[1], "xs"), Some("xs.reverse()".to_string()));
+        assert_eq!(source(&split[0], "xs"), None);
+    }
+}
@@ -4,10 +4,12 @@
 
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::ToTokens;
-use syn::{Attribute, Expr, ExprCall, FnArg, PatType, ReturnType, spanned::Spanned, visit_mut::{self as visitor, VisitMut}};
+use syn::{Attribute, Expr, ExprCall, FnArg, ReturnType, spanned::Spanned, visit_mut::{self as visitor, VisitMut}};
 
 use crate::implementation::{
-    Contract, ContractMode, ContractType, FuncWithContracts,
+    borrowed, chain, commutes_with, concurrent_consistency, deterministic, equivalent_to,
+    inclusion, model, mr_file, mr_seed, pattern, statistical, text, Contract, ContractMode, ContractType,
+    FuncWithContracts,
 };
 use std::collections::HashMap;
 
@@ -21,8 +23,6 @@ pub(crate) struct OldExpr {
 
 #[derive(Debug)]
 pub struct MRRunInfo {
-    /// index of the variable binder.
-    pub index: usize,
     /// index of return number.
     pub retindex1: usize,
     pub retindex2: usize,
@@ -30,19 +30,19 @@ pub struct MRRunInfo {
     mr:ContractType,
     pub variable: Ident,
     pub variable_name: String,
-    pub change: TokenStream,
+    /// field path of the variable that is transformed, e.g. `.id`
+    pub field: Option<TokenStream>,
 }
 
 impl MRRunInfo{
-    fn new(index: usize, retindex1: usize, retindex2: usize, mr:ContractType, variable: Ident, variable_name: String, change: TokenStream)->Self{
+    fn new(retindex1: usize, retindex2: usize, mr:ContractType, variable: Ident, variable_name: String, field: Option<TokenStream>)->Self{
         MRRunInfo{
-            index,
             retindex1,
             retindex2,
             mr,
             variable,
             variable_name,
-            change,
+            field,
         }
     }
 }
@@ -124,6 +124,31 @@ pub(crate) fn extract_old_calls(contracts: &mut [Contract]) -> Vec<OldExpr> {
     };
 
     for contract in contracts {
+        if contract.ty.is_metamorphic() {
+            // modifiers and named arguments of relations can refer to the
+            // state before the call as well
+            for option in contract.options.values_mut() {
+                extractor.visit_expr_mut(option);
+            }
+
+            for stream in contract.streams.iter_mut().skip(1) {
+                // operators are not expressions, so those are skipped
+                let mut expr = match syn::parse2::<Expr>(stream.clone()) {
+                    Ok(expr) => expr,
+                    Err(_) => continue,
+                };
+
+                let extracted = extractor.olds.len();
+                extractor.visit_expr_mut(&mut expr);
+
+                if extractor.olds.len() != extracted {
+                    *stream = expr.into_token_stream();
+                }
+            }
+
+            continue;
+        }
+
         if contract.ty != ContractType::Ensures {
             continue;
         }
@@ -137,7 +162,7 @@ pub(crate) fn extract_old_calls(contracts: &mut [Contract]) -> Vec<OldExpr> {
     extractor.olds
 }
 
-fn get_assert_macro(
+pub(crate) fn get_assert_macro(
     ctype: ContractType, // only Pre/Post allowed.
     mode: ContractMode,
     span: Span,
@@ -177,6 +202,13 @@ fn get_assert_macro(
             (ContractType::Invariant, _) => {
                 panic!("expected Invariant to be narrowed down to Pre/Post")
             }
+            (ContractType::EquivalentTo, _)
+            | (ContractType::Model, _)
+            | (ContractType::CommutesWith, _)
+            | (ContractType::ConcurrentConsistency, _)
+            | (ContractType::Statistical, _) => {
+                panic!("expected {:?} to be narrowed down to Post", ctype)
+            }
             (ContractType::Periodicity, _) => {
                 Some(Ident::new("periodicity", span))
             }
@@ -201,6 +233,12 @@ fn get_assert_macro(
             (ContractType::Mapping, _) => {
                 Some(Ident::new("mapping", span))
             }
+            (ContractType::Deterministic, _) => {
+                Some(Ident::new("deterministic", span))
+            }
+            (ContractType::Inclusion, _) => {
+                Some(Ident::new("inclusion", span))
+            }
         }
     } else {
         match mode {
@@ -218,6 +256,19 @@ struct ParaReplace{
     old_para: String,
 }
 
+/// Rename every binding in `renames` (old name, new name) inside `expr`.
+///
+/// Used to re-instantiate contracts of the original execution for a follow-up
+/// run, where parameters, `ret` and `old()` bindings carry a run suffix.
+pub(crate) fn rename_bindings(expr: &Expr, renames: &[(String, String)]) -> Expr {
+    let mut expr = expr.clone();
+    for (old_para, new_para) in renames {
+        let mut parareplace = ParaReplace { new_para: new_para.clone(), old_para: old_para.clone() };
+        parareplace.visit_expr_mut(&mut expr);
+    }
+    expr
+}
+
 impl VisitMut for ParaReplace {
     fn visit_ident_mut(&mut self, i: &mut Ident) {
         let ident_string = i.to_token_stream().to_string();
@@ -232,21 +283,18 @@ impl VisitMut for ParaReplace {
         // println!("test ident {}", node.to_token_stream().to_string());
         // println!("compare with {:?}", self.old_para);
         if let Expr::Unary(exprunary) = &node {
-            if exprunary.op.to_token_stream().to_string() == String::from("*") && exprunary.expr.to_token_stream().to_string() == self.old_para{
+            if exprunary.op.to_token_stream().to_string() == "*" && exprunary.expr.to_token_stream().to_string() == self.old_para{
                 *node = *exprunary.expr.clone();
             }
         }
         if let Expr::Path(expr) = &node {
             let ident_result = expr.path.get_ident();
-            match ident_result{
-                Some(ident)=> {
-                    let ident_string = ident.to_string();
-                    if ident_string == self.old_para{
-                        // println!("{}", ident_string);
-                        *node = syn::parse_str(self.new_para.as_str()).unwrap();
-                    }
-                },
-                None => (),
+            if let Some(ident) = ident_result {
+                let ident_string = ident.to_string();
+                if ident_string == self.old_para{
+                    // println!("{}", ident_string);
+                    *node = syn::parse_str(self.new_para.as_str()).unwrap();
+                }
             }
             return
         }
@@ -255,6 +303,42 @@ impl VisitMut for ParaReplace {
         visitor::visit_expr_mut(self, node);
         
     }
+
+    // field names are not bindings, `p.x` keeps its field when `x` is renamed
+    fn visit_member_mut(&mut self, _node: &mut syn::Member) {}
+
+    // a shorthand field like `Point { x, y }` is written out when its binding
+    // is renamed
+    fn visit_field_pat_mut(&mut self, node: &mut syn::FieldPat) {
+        if node.colon_token.is_none() && node.pat.to_token_stream().to_string() == self.old_para{
+            node.colon_token = Some(Default::default());
+        }
+        visitor::visit_field_pat_mut(self, node);
+    }
+
+    fn visit_field_value_mut(&mut self, node: &mut syn::FieldValue) {
+        if node.colon_token.is_none() && node.expr.to_token_stream().to_string() == self.old_para{
+            node.colon_token = Some(Default::default());
+        }
+        visitor::visit_field_value_mut(self, node);
+    }
+}
+
+/// The bindings that are renamed for the follow-up run `run_index`: the
+/// transformed variable, all cloned mutable parameters, `ret` and the `old()`
+/// snapshots.
+fn run_renames(key: &str, mut_para: &[String], olds: &[OldExpr], run_index: usize) -> Vec<(String, String)> {
+    let mut renames = vec![(key.to_string(), format!("{}{}{}", key, "_contract_", run_index))];
+    for para in mut_para{
+        if para != key && !para.is_empty(){
+            renames.push((para.clone(), format!("{}{}{}", para, "_contract_", run_index)));
+        }
+    }
+    renames.push((String::from("ret"), format!("{}{}", "ret", run_index)));
+    for old in olds{
+        renames.push((old.name.clone(), format!("{}_{}", old.name, run_index)));
+    }
+    renames
 }
 
 /// Generate the resulting code for this function by inserting assertions.
@@ -265,6 +349,21 @@ pub(crate) fn generate(
 ) -> TokenStream {
     let func_name = func.function.sig.ident.to_string();
 
+    // random number generators are not shared with the follow-up runs, every
+    // follow-up run gets a copy of the generator of the original call instead
+    let seeded = match mr_seed::take_seeded(&mut func.function){
+        Ok(seeded) => seeded,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    // chains of transformations that can't be used are reported before any
+    // follow-up run is made for them
+    for c in &func.contracts{
+        if let Some(Err(err)) = chain::steps(c){
+            return err.to_compile_error();
+        }
+    }
+
     // creates an assertion appropriate for the current mode
     let make_assertion = |mode: ContractMode,
                           ctype: ContractType,
@@ -283,8 +382,7 @@ pub(crate) fn generate(
                     if !(#exec_expr) {
                         log::error!(#format_args);
                     }
-                }
-                .into_iter(),
+                },
             );
         }
 
@@ -292,8 +390,7 @@ pub(crate) fn generate(
             result.extend(
                 quote::quote_spanned! { span=>
                     #assert_macro!(#exec_expr, #format_args);
-                }
-                .into_iter(),
+                },
             );
         }
 
@@ -327,8 +424,7 @@ pub(crate) fn generate(
                     if !(#exec_expr) {
                         log::error!(#format_args);
                     }
-                }
-                .into_iter(),
+                },
             );
         }
 
@@ -336,8 +432,7 @@ pub(crate) fn generate(
             result.extend(
                 quote::quote_spanned! { span=>
                     #assert_macro!(#display, #format_args);
-                }
-                .into_iter(),
+                },
             );
         }
 
@@ -414,71 +509,45 @@ pub(crate) fn generate(
         variable_type.insert(String::from("ret"), output_type.clone().to_token_stream().to_string().split(" ").last().unwrap().to_string());
     }
 
-    // add input elements for variable type
+    // add input elements for variable type, every name bound by a pattern
+    // is a variable of its own
     let function_signature = func.function.sig.clone();
-    let _result: Vec<usize> = function_signature.inputs.iter().
-    map(|arg|{
-        match arg {
-            FnArg::Typed(PatType { ty, pat, .. }) => {
-                variable_type.insert(pat.to_token_stream().to_string().split(" ").last().expect("no variable name here").to_string(), ty.clone().to_token_stream().to_string().split(" ").last().expect("input para type parse error").to_string());
-                1
-            },
-            _ => 0,
-        }
-    }).collect();
-    println!("variable_type {:?}", variable_type);
-    // a vector for mutable variable for clone perparation
-    let mut_para:Vec<_> = function_signature.inputs.iter().
-    filter(|arg|{
-        
-        match arg {
-            FnArg::Receiver(r) => r.mutability != None,
-            FnArg::Typed(PatType { ty, pat, .. })=> {
-                let mut returns:bool = false;
-                match &**pat{
-                    syn::Pat::Ident(i)=>{
-                        returns = i.mutability != None;
-                    },
-                    _=>{}
+    for arg in function_signature.inputs.iter(){
+        if let FnArg::Typed(pat_type) = arg {
+            for binding in pattern::bindings(pat_type){
+                let ty = match &binding.ty{
+                    Some(ty) => ty.to_token_stream().to_string().split(" ").last().expect("input para type parse error").to_string(),
+                    None => String::from("_"),
                 };
-                match &**ty{
-                    syn::Type::Reference(tr) => {
-                        returns = returns | (tr.mutability != None);
-                    },
-                    _ => {}
-                };
-                returns
+                variable_type.insert(binding.ident.to_string(), ty);
             }
         }
     }
-        ).
-    map(|arg|{
+    // the stand-in input of a function without parameters
+    variable_type.insert(String::from(deterministic::UNIT), String::from("()"));
+    println!("variable_type {:?}", variable_type);
+    // a vector for mutable variable for clone perparation
+    let mut_para:Vec<_> = function_signature.inputs.iter().
+    flat_map(|arg|{
         match arg {
             FnArg::Receiver(r) => {
                 match r.mutability {
-                    Some(_) => "self".to_string(),
-                    None=> String::new(),
+                    Some(_) => vec!["self".to_string()],
+                    None=> vec![],
                 }
             }
-            FnArg::Typed(PatType { pat, .. }) => {
-                // match &**ty{
-                //     syn::Type::Reference(tr)=> {
-                //         match (tr.mutability,&**pat) {
-                //             (Some(_),_) => pat.to_token_stream().to_string(),
-                //             (None,_) => {
-                //                 String::new()
-                //             },
-                //         }
-                //     },
-                //     _ => String::new()
-                // }
-                match &**pat{
-                    syn::Pat::Ident(i)=>{i.ident.to_string()},
-                    _=> String::new(),
+            FnArg::Typed(pat_type) => {
+                let by_mut_ref = matches!(&*pat_type.ty, syn::Type::Reference(tr) if tr.mutability.is_some());
+                match &*pat_type.pat{
+                    syn::Pat::Ident(i) if by_mut_ref => vec![i.ident.to_string()],
+                    _ => pattern::bindings(pat_type).into_iter().
+                        filter(|binding| binding.mutable).
+                        map(|binding| binding.ident.to_string()).collect(),
                 }
             }
         }}
-    ).collect();
+    ).
+    filter(|para| !seeded.iter().any(|rng| rng == para)).collect();
     println!("mut_para {:?}", mut_para);
 
     // FnArg::Typed(PatType { pat, .. }) => {
@@ -493,35 +562,35 @@ pub(crate) fn generate(
     //     }
     // }
     let self_type = String::from("struct");
+    let field_type = String::from("field");
 
     // clone and modify the variable used in the relationship
     let mut clone_variable = proc_macro2::TokenStream::new();
     let mut index = 0;
-    let modify_para: proc_macro2::TokenStream = func
+    let modify_para: HashMap<usize, TokenStream> = func
         .contracts
         .iter().enumerate()
         .filter(|pair| {
             let c = pair.1;
-            c.ty == ContractType::Periodicity || c.ty == ContractType::AddNotEqual || c.ty ==ContractType::DimensionTrans
-            || c.ty == ContractType::Homomorphism || c.ty == ContractType::Monotonicity || c.ty == ContractType::IterConsistency
-            || c.ty == ContractType::Symmetry || c.ty == ContractType::Mapping
+            c.ty.is_metamorphic()
         })
-        .flat_map(|pair| {
+        .map(|pair| {
             let c = pair.1;
             // println!("{:?}", c.streams);
             let contract_index = pair.0;
             let para = c.streams.first().expect("No para here").clone();
-            let para_string = para.to_string();
+            // a field path like `book.id` transforms only the field of a clone
+            // of the root variable
+            let (para_string, field) = split_field_path(&para);
             let mut para_clone = para_string.clone();
             let mut _para_clone2 = para_string.clone();
-            para_clone.push_str(format!("{}{}","_contract_", (index + 1).to_string()).as_str());
+            para_clone.push_str(format!("{}{}","_contract_", (index + 1)).as_str());
             let para_number = c.streams.len();
-            let modi:TokenStream;
-            if para_number > 2{
-                modi = c.streams[2].clone();
+            let modi:TokenStream = if para_number > 2{
+                c.streams[2].clone()
             }else{
-                modi = TokenStream::new();
-            }
+                TokenStream::new()
+            };
             let span = c.streams[0].span();
             let para = syn::Ident::new(&para_string, span);
             let para_clone = syn::Ident::new(&para_clone, span);
@@ -532,48 +601,89 @@ pub(crate) fn generate(
             
             let ret_type = variable_type.get(&String::from("ret")).expect("No ret type in variable type");
             let mut para_type;
-            if para_string == String::from("self"){
+            if field.is_some(){
+                para_type = &field_type;
+            }
+            else if para_string == "self"{
                 para_type = &self_type;
             }
             else{
                 para_type = variable_type.get(&para_string).expect("No para type in variable type");
             }
+            // a borrowed parameter is transformed as an owned value, which the
+            // follow-up run borrows again
+            let referent = borrowed::referent(&func.function, &para_string);
+            let owned_type;
+            if referent.is_some() && field.is_none(){
+                owned_type = borrowed::owned_type(para_type);
+                para_type = &owned_type;
+            }
             let sym_number = String::from("sym_number");
             let sym_float = String::from("sym_float");
             let sym_func = String::from("sym_func");
             let sym_bool = String::from("sym_bool");
-            match (c.ty, c) {
+            // bind `target` to the transformation of `source`, either directly
+            // or as the field of a clone of the root variable
+            let bind = |source: &syn::Ident, target: &syn::Ident, make: &dyn Fn(&syn::Ident, &syn::Ident, &TokenStream) -> TokenStream| -> TokenStream {
+                match &field{
+                    None if referent.is_some() && *source == para => {
+                        let owned = syn::Ident::new(&format!("__contract_owned_{}", source), span);
+                        let to_owned = borrowed::to_owned(source, &owned);
+                        let binding = make(&owned, target, &mut_def);
+                        quote::quote! {
+                            #to_owned
+                            #binding
+                        }
+                    },
+                    None => make(source, target, &mut_def),
+                    Some(field) => field_statement(&para, field, source, target, make),
+                }
+            };
+            // a chain of transformations replaces the operator and the modifier
+            let steps = match chain::steps(c){
+                Some(Ok(steps)) => Some(steps),
+                Some(Err(err)) => return (contract_index, err.to_compile_error()),
+                None => None,
+            };
+            let follow_up = |ident_type: &str, source: &syn::Ident, target: &syn::Ident, mut_def: &TokenStream| -> TokenStream {
+                let op = c.streams.get(1).cloned().unwrap_or_default();
+                match &steps{
+                    Some(steps) => chain::follow_up(ident_type, source, target, steps, mut_def),
+                    None => merge_statement(ident_type, source, &modi, &op, mut_def, target),
+                }
+            };
+            let binding = match (c.ty, c) {
                 (ContractType::IterConsistency, _) => {
-                    if *para_type != self_type{
+                    if *para_type != self_type && *para_type != field_type{
                         debug_assert!(ret_type == para_type, "wrong type, consistency can not satisfied");
                     }
                     let mut para_old = para_string.clone();
                     para_old.push_str("_contract_old");
-                    let mr = MRRunInfo::new(contract_index, index + 1, 0, c.ty, para.clone(),para_string, modi.clone());
+                    let mr = MRRunInfo::new(index + 1, 0, c.ty, para.clone(),para_string, field.clone());
                     index += 1;
                     run_map.insert(contract_index, mr);
                     let para_old = syn::Ident::new(&para_old, span);
                     
                     let binding = quote::quote! {
-                        let #para_old = #para.clone();
+                        let #para_old = #para #field.clone();
                     };            
                     binding
                 }
                 (ContractType::Homomorphism, _) => {
                     let op = c.streams[1].clone();
-                    _para_clone2.push_str(format!("{}{}","_contract_", (index + 2).to_string()).as_str());
+                    _para_clone2.push_str(format!("{}{}","_contract_", (index + 2)).as_str());
                     let para_clone2 = syn::Ident::new(&_para_clone2, span);
-                    let mr = MRRunInfo::new(contract_index, index + 1, index + 2, c.ty, para.clone(),para_string, modi.clone());
+                    let mr = MRRunInfo::new(index + 1, index + 2, c.ty, para.clone(),para_string, field.clone());
                     run_map.insert(contract_index, mr);
                     index += 2;
                     let mut first_modi = modi.clone();
                     if para_type == "String"{
                         first_modi = quote::quote! {#modi.to_string();}
                     }
-                    let first_assign = quote::quote! {
-                        let #mut_def #para_clone = #first_modi;
-                    };
-                    let second_assign = merge_statement(para_type.as_str(), &para, &modi, &op, &mut_def, &para_clone2);
+                    let first_assign = bind(&para, &para_clone, &|_, target, mut_def| quote::quote! {
+                        let #mut_def #target = #first_modi;
+                    });
+                    let second_assign = bind(&para, &para_clone2, &|source, target, mut_def| merge_statement(para_type.as_str(), source, &modi, &op, mut_def, target));
                     let binding = quote::quote! {
                         #first_assign
                         #second_assign
@@ -581,21 +691,49 @@ pub(crate) fn generate(
                     binding
                 }
                 (ContractType::Mapping, _) => {
-                    let op = c.streams[1].clone();
-                    _para_clone2.push_str(format!("{}{}","_contract_", (index + 2).to_string()).as_str());
+                    _para_clone2.push_str(format!("{}{}","_contract_", (index + 2)).as_str());
                     let para_clone2 = syn::Ident::new(&_para_clone2, span);
-                    let mr = MRRunInfo::new(contract_index, index + 1, index + 2, c.ty, para.clone(),para_string, modi.clone());
+                    let mr = MRRunInfo::new(index + 1, index + 2, c.ty, para.clone(),para_string, field.clone());
                     // println!("{:?}", mr);
                     run_map.insert(contract_index, mr);
                     index += 2;
-                    let first_assign = merge_statement(para_type.as_str(), &para, &modi, &op, &mut_def, &para_clone);
-                    let second_assign = merge_statement(para_type.as_str(), &para_clone, &modi, &op, &mut_def, &para_clone2);
+                    let first_assign = bind(&para, &para_clone, &|source, target, mut_def| follow_up(para_type.as_str(), source, target, mut_def));
+                    let second_assign = bind(&para_clone, &para_clone2, &|source, target, mut_def| follow_up(para_type.as_str(), source, target, mut_def));
                     let binding = quote::quote! {
                         #first_assign
                         #second_assign
                     };
                     binding
                 }
+                (ContractType::Deterministic, _) => {
+                    let is_unit = para_string == deterministic::UNIT;
+                    let mr = MRRunInfo::new(index + 1, 0, c.ty, para.clone(),para_string, field.clone());
+                    index += 1;
+                    run_map.insert(contract_index, mr);
+                    // a function without parameters is just run again
+                    if is_unit{
+                        return (contract_index, quote::quote! {
+                            #[allow(unused_variables)]
+                            let #para_clone = ();
+                        });
+                    }
+                    // the identity transformation, whose result is unused if the
+                    // body only names the variable inside of macros
+                    bind(&para, &para_clone, &|source, target, mut_def| quote::quote! {
+                        #[allow(unused_variables)]
+                        let #mut_def #target = #source.clone();
+                    })
+                }
+                (ContractType::Inclusion, _) => {
+                    let transform = c.streams.get(1).cloned().unwrap_or_default();
+                    let mr = MRRunInfo::new(index + 1, 0, c.ty, para.clone(),para_string, field.clone());
+                    index += 1;
+                    run_map.insert(contract_index, mr);
+                    bind(&para, &para_clone, &|source, target, mut_def| match &steps{
+                        Some(_) => follow_up(para_type.as_str(), source, target, mut_def),
+                        None => inclusion::follow_up_input(source, target, &transform, mut_def),
+                    })
+                }
                 (_,_) => {
                     let op = c.streams[1].clone();
                     println!("{}",para_type);
@@ -609,14 +747,17 @@ pub(crate) fn generate(
                         else if "bool" == para_type.as_str(){
                             para_type = &sym_bool;
                         }
-                        else if ["String", "str"].contains(&para_type.as_str()){
-                            panic!("undefined symmetry for string");
+                        else if "String" == para_type.as_str(){
+                            // strings are only mirrored by the text transformations
+                            if text::transform_name(&op).is_none() && steps.is_none(){
+                                panic!("undefined symmetry for string, use one of the text transformations {:?}", text::TRANSFORMS);
+                            }
                         }
                         else{
                             para_type = &sym_func;
                         }
                     }
-                    let mr = MRRunInfo::new(contract_index, index + 1, 0, c.ty, para.clone(),para_string, modi.clone());
+                    let mr = MRRunInfo::new(index + 1, 0, c.ty, para.clone(),para_string, field.clone());
                     index += 1;
                     run_map.insert(contract_index, mr);
                     let mut binding = TokenStream::new();
@@ -627,31 +768,22 @@ pub(crate) fn generate(
                             let #modi_old = #modi.clone();
                         });
                     }
-                    binding.extend(merge_statement(para_type.as_str(), &para, &modi, &op, &mut_def, &para_clone));
+                    binding.extend(bind(&para, &para_clone, &|source, target, mut_def| follow_up(para_type.as_str(), source, target, mut_def)));
                     // let binding = quote::quote! {
                     //     let #para_clone = format!("{}{}", #para , #modi);
                     //     let #mut_def #para_clone = #para_clone.as_str();
                     // };     
                     binding
                 }
-            }
-            
+            };
+            (contract_index, binding)
         }).collect();
-        clone_variable.extend(modify_para);
         // println!("clone_variable:{}", clone_variable);
 
     let mut result_unwrapped = false;
+    let projected_type = String::from("projection");
     //  generate corresponding assertions based on mr
-    let mr: proc_macro2::TokenStream = func
-        .contracts
-        .iter().enumerate()
-        .filter(|pair| {
-            let c = pair.1;
-            c.ty == ContractType::Periodicity || c.ty == ContractType::AddNotEqual || c.ty ==ContractType::DimensionTrans
-            || c.ty == ContractType::Homomorphism || c.ty == ContractType::Monotonicity || c.ty == ContractType::IterConsistency
-            || c.ty == ContractType::Symmetry || c.ty == ContractType::Mapping
-        })
-        .flat_map(|pair| {
+    let mut mr_assertion = |pair: (usize, &Contract)| -> TokenStream {
             let c = pair.1;
             // println!("{:?}", c);
             let contract_index = pair.0;
@@ -676,17 +808,44 @@ pub(crate) fn generate(
 
             let mode = c.mode.final_mode();
 
-            let ret_type = variable_type.get(&String::from("ret")).unwrap();
-            let mut op = c.streams[1].clone();
+            // results that are only equal up to rounding are compared with a
+            // tolerance
+            let tolerance = c.option("tolerance");
+            let assert_relation = |mode: ContractMode,
+                                   ctype: ContractType,
+                                   display: TokenStream,
+                                   exec_expr: &str,
+                                   desc: &str| {
+                make_str_assertion(mode, ctype, within_tolerance(display, tolerance), exec_expr, desc)
+            };
+
+            // a relation over the post-state or a part of the return value
+            // compares projections instead, so their type is not known here
+            let projection = c.option("output");
+            let selector = c.option("on");
+            // a guarded relation is checked in its own block before the others,
+            // so it must unwrap the return value itself
+            let guarded = c.option("when").is_some();
+            let unwrapped_before = result_unwrapped;
+            if guarded{
+                result_unwrapped = false;
+            }
+            let ret_type = if projection.is_some() || selector.is_some(){
+                &projected_type
+            }
+            else{
+                variable_type.get(&String::from("ret")).unwrap()
+            };
+            let mut op = c.streams.get(1).cloned().unwrap_or_default();
             let mut extra_op = TokenStream::new();
             if c.streams.len() > 3{
                 extra_op = c.streams[3].clone();
                 match ret_type.as_str(){
-                    "f32"| "f64"| "i128"| "i16"| "i32"| "i64"| "i8"| "isize"| "u128"| "u16"| "u32"| "u64"| "u8"| "usize" =>{
-                        if op.to_string().chars().nth(0).expect("operator not implement correctly").is_ascii_alphabetic(){
+                    "f32"| "f64"| "i128"| "i16"| "i32"| "i64"| "i8"| "isize"| "u128"| "u16"| "u32"| "u64"| "u8"| "usize"
+                        // a chain of transformations leaves the operator empty
+                        if matches!(op.to_string().chars().next(), Some(first) if first.is_ascii_alphabetic())=> {
                             op = proc_macro2::Punct::new(char::from(43), proc_macro2::Spacing::Alone).to_token_stream();
-                        }
-                    },
+                        },
                     _ => (),
                 }
             }
@@ -696,7 +855,18 @@ pub(crate) fn generate(
             }
             let span = Span::call_site();
             let ret1 = syn::Ident::new(format!("{}{}", "ret", second_run_index).as_str(), span);
-            let ret_str_ident = syn::Ident::new("ret", span);
+            // a step of a chain that is compared step by step is compared with
+            // the output of the step before, which is the previous contract
+            let source_run = match c.step{
+                Some(step) if step > 0 => run_map.get(&(contract_index - 1)).expect("No run for the step before").retindex1,
+                _ => 0,
+            };
+            let ret_str_ident = if source_run == 0{
+                syn::Ident::new("ret", span)
+            }
+            else{
+                syn::Ident::new(format!("{}{}", "ret", source_run).as_str(), span)
+            };
             let mut ret0_unwrap:TokenStream = TokenStream::new();
 
             let ret = match (c.ty, c) {
@@ -705,10 +875,10 @@ pub(crate) fn generate(
                         ret0_unwrap = unwrap_return(ret_type, &ret_str_ident);
                     }
                     let ret1_unwrap = unwrap_return(ret_type, &ret1);
-                    let assert_stream = make_str_assertion(
+                    let assert_stream = assert_relation(
                         mode,
                         ContractType::Periodicity,
-                        quote::quote! {#ret1 == ret},
+                        quote::quote! {#ret1 == #ret_str_ident},
                         "f(x + t) = f(x)",
                         &desc.clone(),
                     );
@@ -720,10 +890,10 @@ pub(crate) fn generate(
                     }
                 }
                 (ContractType::AddNotEqual, _) => {
-                    make_str_assertion(
+                    assert_relation(
                         mode,
                         ContractType::AddNotEqual,
-                        quote::quote! {#ret1 != ret},
+                        quote::quote! {#ret1 != #ret_str_ident},
                         "f(x + y) != f(x)",
                         &desc.clone(),
                     )
@@ -739,17 +909,16 @@ pub(crate) fn generate(
                         ret0_unwrap = unwrap_return(ret_type, &ret_str_ident);
                     }
                     let ret1_unwrap = unwrap_return(ret_type, &ret1);
-                    let a:TokenStream;
-                    if ! extra_modi.is_empty(){
+                    let a:TokenStream = if ! extra_modi.is_empty(){
                         let extra_modi_ident:TokenStream = syn::parse2(extra_modi).expect("extra modi can't parse");
-                        a = merge_expr(ret_type, &ret_str_ident, &extra_modi_ident, &extra_op);
+                        merge_expr(ret_type, &ret_str_ident, &extra_modi_ident, &extra_op)
                     }
                     else{
-                        a = merge_expr(ret_type, &ret_str_ident, &modi_contract_old.to_token_stream(), &extra_op);
-                    }
+                        merge_expr(ret_type, &ret_str_ident, &modi_contract_old.to_token_stream(), &extra_op)
+                    };
                     let asserts = quote::quote! {#ret1 == #a};
                     // let asserts = quote::quote! {ret + #x == #ret1};
-                    let assert_stream = make_str_assertion(
+                    let assert_stream = assert_relation(
                         mode,
                         ContractType::DimensionTrans,
                         asserts,
@@ -784,8 +953,8 @@ pub(crate) fn generate(
                     }
                     let ret1_unwrap = unwrap_return(ret_type, &ret1);
                     result_unwrapped = true;
-                    let asserts = quote::quote! {ret == #sign #ret1};
-                    let assert_stream = make_str_assertion(
+                    let asserts = quote::quote! {#ret_str_ident == #sign #ret1};
+                    let assert_stream = assert_relation(
                         mode,
                         ContractType::Symmetry,
                         asserts,
@@ -811,7 +980,7 @@ pub(crate) fn generate(
                     let asserts = quote::quote! {#a == #ret2};
                     // let asserts = quote::quote! {ret == #ret1 + #ret2};
                     result_unwrapped = true;
-                    let assert_stream = make_str_assertion(
+                    let assert_stream = assert_relation(
                         mode,
                         ContractType::Homomorphism,
                         asserts,
@@ -840,7 +1009,7 @@ pub(crate) fn generate(
                     let asserts = quote::quote! {#a == #b};
                     // let asserts = quote::quote! {ret + ret == #ret1 + #para};
                     result_unwrapped = true;
-                    let assert_stream = make_str_assertion(
+                    let assert_stream = assert_relation(
                         mode,
                         ContractType::IterConsistency,
                         asserts,
@@ -866,7 +1035,7 @@ pub(crate) fn generate(
                     result_unwrapped = true;
                     let asserts = merge_expr(ret_type, &ret_str_ident, &ret1.to_token_stream(), &extra_op);
                     // asserts = quote::quote! {ret #extra_op #ret1};
-                    let assert_stream = make_str_assertion(
+                    let assert_stream = assert_relation(
                         mode,
                         ContractType::Monotonicity,
                         asserts,
@@ -895,7 +1064,7 @@ pub(crate) fn generate(
                     let b = merge_expr(ret_type, &ret2, &ret1.to_token_stream(), &extra_op);
                     let asserts = quote::quote! {#a == #b};
                     result_unwrapped = true;
-                    let assert_stream = make_str_assertion(
+                    let assert_stream = assert_relation(
                         mode,
                         ContractType::Mapping,
                         asserts,
@@ -909,55 +1078,173 @@ pub(crate) fn generate(
                         #assert_stream
                     }
                 }
+                (ContractType::Deterministic, _) => {
+                    // compare with `ret` as it was left by the other relations
+                    let mut ret1_unwrap = TokenStream::new();
+                    if result_unwrapped{
+                        ret1_unwrap = unwrap_return(ret_type, &ret1);
+                    }
+                    let mut assert_stream = assert_relation(
+                        mode,
+                        ContractType::Deterministic,
+                        quote::quote! {#ret1 == ret},
+                        "f(x) = f(x)",
+                        &desc.clone(),
+                    );
+                    for (name, actual) in deterministic::mutated_inputs(c, &func.function){
+                        let clone = syn::Ident::new(format!("{}{}{}", name, "_contract_", second_run_index).as_str(), span);
+                        assert_stream.extend(assert_relation(
+                            mode,
+                            ContractType::Deterministic,
+                            quote::quote! {#clone == #actual},
+                            &format!("{} after f(x) = {} after f(x)", name, name),
+                            &desc.clone(),
+                        ));
+                    }
+                    quote::quote! {
+                        #ret1_unwrap
+                        #assert_stream
+                    }
+                }
+                (ContractType::Inclusion, _) => {
+                    let direction = match inclusion::Direction::of(c){
+                        Ok(direction) => direction,
+                        Err(err) => return err.to_compile_error(),
+                    };
+                    if ! result_unwrapped{
+                        ret0_unwrap = unwrap_return(ret_type, &ret_str_ident);
+                    }
+                    let ret1_unwrap = unwrap_return(ret_type, &ret1);
+                    result_unwrapped = true;
+                    let (sub, sup) = match direction{
+                        inclusion::Direction::Superset => (ret_str_ident.to_token_stream(), ret1.to_token_stream()),
+                        inclusion::Direction::Subset => (ret1.to_token_stream(), ret_str_ident.to_token_stream()),
+                    };
+                    let assert_stream = assert_relation(
+                        mode,
+                        ContractType::Inclusion,
+                        inclusion::contains_all(&sub, &sup),
+                        direction.relation(),
+                        &desc.clone(),
+                    );
+                    quote::quote! {
+                        #ret0_unwrap
+                        #ret1_unwrap
+                        #assert_stream
+                    }
+                }
                 (_,_) => {
                     println!("not a mr relation");
                     TokenStream::new()
                 }
             };
-            
+
+            if projection.is_some() || selector.is_some(){
+                // shadow the return values with the projected post-states and
+                // then with the selected part of them
+                result_unwrapped = unwrapped_before;
+                let mut runs = vec![(ret_str_ident.clone(), source_run)];
+                for run_index in [mr_info.retindex1, mr_info.retindex2]{
+                    if run_index != 0{
+                        runs.push((syn::Ident::new(format!("{}{}", "ret", run_index).as_str(), span), run_index));
+                    }
+                }
+                let mut bindings = TokenStream::new();
+                for (ret_run, run_index) in runs{
+                    if let Some(output) = projection{
+                        let output_run = if run_index == 0{
+                            output.clone()
+                        }
+                        else{
+                            rename_bindings(output, &run_renames(&mr_info.variable_name, &mut_para, &olds, run_index))
+                        };
+                        bindings.extend(quote::quote! {
+                            let #ret_run = (#output_run).to_owned();
+                        });
+                    }
+                    if let Some(on) = selector{
+                        let on_run = select_on(on, &ret_run);
+                        bindings.extend(quote::quote! {
+                            let #ret_run = #on_run;
+                        });
+                    }
+                }
+                return quote::quote! {
+                    {
+                        #bindings
+                        #ret
+                    }
+                };
+            }
+
+            if guarded{
+                result_unwrapped = unwrapped_before;
+                if ["Option", "Result"].contains(&ret_type.as_str()){
+                    // do not move the return value out of the function result
+                    return quote::quote! {
+                        let ret = ret.clone();
+                        #ret
+                    };
+                }
+            }
+
             ret
-        })
-        .collect();
+    };
+    let mut mr = TokenStream::new();
+    let mut guarded_mr: HashMap<usize, TokenStream> = HashMap::new();
+    for pair in func.contracts.iter().enumerate(){
+        if !pair.1.ty.is_metamorphic(){
+            continue;
+        }
+        let assertion = mr_assertion(pair);
+        if pair.1.option("when").is_some(){
+            guarded_mr.insert(pair.0, assertion);
+        }
+        else{
+            mr.extend(assertion);
+        }
+    }
         // println!("{}", mr);
 
 
     let mut_para1 = mut_para.clone();
 
-    let mut clone_mut = proc_macro2::TokenStream::new();
     // println!("{:?}", run_map);
     // println!("{:?}", index);
+    let mut guarded_inputs: HashMap<usize, Vec<Ident>> = HashMap::new();
     for run_ind in 0..func.contracts.len(){
         let mr_info_result = run_map.get(&run_ind);
         let mr_info = match mr_info_result {
             Some(info) => info,
             None => continue,
         };
-        let run_index = mr_info.retindex1.clone();
+        let mut clone_mut = modify_para.get(&run_ind).cloned().unwrap_or_default();
+        let run_index = mr_info.retindex1;
         let para_string = mr_info.variable_name.clone();
         for para in &mut_para{
             if para_string == *para{
                 continue;
             }
             let mut para_clone = para.clone();
-            para_clone.push_str(format!("{}{}","_contract_", run_index.to_string()).as_str());
+            para_clone.push_str(format!("{}{}","_contract_", run_index).as_str());
             let span = clone_mut.span();
-            let para = syn::Ident::new(&para, span);
+            let para = syn::Ident::new(para, span);
             let para_clone = syn::Ident::new(&para_clone, span);
             let binding = quote::quote! {
                 let mut #para_clone = #para .clone();
             };
             clone_mut.extend(Some(binding));
         }
-        let run_index2 = mr_info.retindex2.clone();
+        let run_index2 = mr_info.retindex2;
         if run_index2 != 0{
             for para in &mut_para{
                 if para_string == *para{
                     continue;
                 }
                 let mut para_clone = para.clone();
-                para_clone.push_str(format!("{}{}","_contract_", run_index2.to_string()).as_str());
+                para_clone.push_str(format!("{}{}","_contract_", run_index2).as_str());
                 let span = clone_mut.span();
-                let para = syn::Ident::new(&para, span);
+                let para = syn::Ident::new(para, span);
                 let para_clone = syn::Ident::new(&para_clone, span);
                 let binding = quote::quote! {
                     let mut #para_clone = #para .clone();
@@ -965,8 +1252,27 @@ pub(crate) fn generate(
                 clone_mut.extend(Some(binding));
             }
         }
+
+        // the inputs of a guarded relation are only prepared when the guard
+        // holds for the source inputs
+        match func.contracts[run_ind].option("when"){
+            Some(guard) => {
+                let inputs = syn::Ident::new(format!("{}{}", "__contract_inputs_", run_ind).as_str(), Span::call_site());
+                let names = bound_names(&clone_mut);
+                clone_variable.extend(quote::quote! {
+                    let #inputs = if #guard {
+                        #clone_mut
+                        Some((#(#names,)*))
+                    } else {
+                        None
+                    };
+                });
+                guarded_inputs.insert(run_ind, names);
+            },
+            None => clone_variable.extend(clone_mut),
+        }
     }
-    // println!("clone def: {}", clone_mut);
+    // println!("clone def: {}", clone_variable);
         
     //
     // generate assertion code for post-conditions
@@ -1010,15 +1316,15 @@ pub(crate) fn generate(
     // bind "old()" expressions
     //
 
-    let olds = {
+    let old_bindings = {
         let mut toks = proc_macro2::TokenStream::new();
 
-        for old in olds {
+        for old in &olds {
             let span = old.expr.span();
 
             let name = syn::Ident::new(&old.name, span);
 
-            let expr = old.expr;
+            let expr = &old.expr;
 
             let binding = quote::quote_spanned! { span=>
                 let #name = #expr;
@@ -1049,17 +1355,52 @@ pub(crate) fn generate(
         ReturnType::Default => quote::quote! {},
     };
 
-    let body = quote::quote! {
-        #[allow(unused_mut)]
-        let mut run = || #ret_ty #block;
+    let body = if borrowed::returns_reference(&func.function){
+        // the returned reference may borrow from a mutable reference, which
+        // the run uses through a reborrow
+        let references = borrowed::unique_references(&func.function);
+        let renames: Vec<_> = references.iter().map(|reference| (reference.to_string(), borrowed::reborrow_name(reference).to_string())).collect();
+        let block = syn::parse2::<Expr>(block.to_token_stream()).expect("function body does not pass compiler");
+        let block = match rename_bindings(&block, &renames){
+            Expr::Block(block) => block.block,
+            _ => unreachable!("the function body is a block"),
+        };
+        borrowed::run_once(&syn::Ident::new("ret", Span::call_site()), &ret_ty, &block, &references)
+    }
+    else{
+        quote::quote! {
+            #[allow(unused_mut)]
+            let mut run = || #ret_ty #block;
 
-        let ret = run();
+            let ret = run();
+        }
     };
 
+    // the reference implementations, models, commuting calls, threaded calls
+    // and samples run on the inputs before the body does
+    let mut oracle_calls = TokenStream::new();
+    let mut oracle_checks = TokenStream::new();
+    for (index, contract) in func.contracts.iter().enumerate(){
+        let (call, checks) = match contract.ty{
+            ContractType::EquivalentTo => equivalent_to::differential(contract, index, &func.function),
+            ContractType::Model => model::mirror(contract, index, &func.function),
+            ContractType::CommutesWith => (commutes_with::sequences(contract, index, &func.function), TokenStream::new()),
+            ContractType::ConcurrentConsistency => concurrent_consistency::parallel_runs(contract, index, &func.function),
+            ContractType::Statistical => (statistical::samples(contract, index, &func.function, &seeded), TokenStream::new()),
+            _ => continue,
+        };
+        oracle_calls.extend(call);
+        oracle_checks.extend(checks);
+    }
+
     let perpare_and_body = quote::quote! {
             #pre
 
+            #oracle_calls
+
             #body
+
+            #oracle_checks
     };
     // println!("original body: {}", perpare_and_body);
 
@@ -1076,19 +1417,31 @@ pub(crate) fn generate(
             Some(info) => info,
             None => continue,
         };
+        let mut runs_body = TokenStream::new();
         let second_run_index = mr_info.retindex1;
         println!("generating mr: {:?}", mr_info.mr);
         let clone_last:TokenStream = match mr_info.mr {
             ContractType::IterConsistency => {
                 let span = Span::call_site();
                 let key = &mr_info.variable.to_string();
-                let para1 = syn::Ident::new(format!("{}{}{}", mr_info.variable.to_string(), "_contract_", second_run_index).as_str(), span);
+                let para1 = syn::Ident::new(format!("{}{}{}", mr_info.variable, "_contract_", second_run_index).as_str(), span);
                 let mut mutstr = TokenStream::new(); 
                 if mut_para.contains(key){
                     mutstr = quote::quote! { mut };
                 }
-                quote::quote! {
-                    let #mutstr #para1 = ret.clone();
+                let last = borrowed::owned_result(&func.function, &syn::Ident::new("ret", span));
+                match &mr_info.field{
+                    Some(field) => {
+                        let para = &mr_info.variable;
+                        quote::quote! {
+                            #[allow(unused_mut)]
+                            let mut #para1 = #para.clone();
+                            #para1 #field = #last;
+                        }
+                    },
+                    None => quote::quote! {
+                        let #mutstr #para1 = #last;
+                    },
                 }
             },
             _ => quote::quote! {},
@@ -1129,7 +1482,7 @@ pub(crate) fn generate(
                         let mut expr_clone = expr.clone();
                         let ex = &mut expr_clone;
                         parareplace.visit_expr_mut(ex);
-                        println!("{}", ex.to_token_stream().to_string());
+                        println!("{}", ex.to_token_stream());
                         // let expr = expr.into_token_stream().to_string().replace(
                         //     format!("{}", keyclone).as_str(), format!(" {}{}{} ", keyclone, "_contract_", second_run_index).as_str());
                         // // expr = expr.replace("\n", "");
@@ -1146,7 +1499,7 @@ pub(crate) fn generate(
                             mode,
                             ContractType::Requires,
                             display.clone(),
-                            &ex,
+                            ex,
                             &desc.clone(),
                         )
                     },
@@ -1162,6 +1515,21 @@ pub(crate) fn generate(
         // println!("{}", block_attrs_string);
         
         let mut block_attrs = syn::parse2::<Expr>(block_attrs.to_token_stream()).expect("function body does not pass compiler");
+        // the variables this run has clones of
+        let mut cloned = mut_para1.clone();
+        if !cloned.contains(key){
+            cloned.push(key.clone());
+        }
+        // the parts of a whole argument `whole @ (a, b)` are bound again from
+        // its clone, the renaming below turns them into the names of this run
+        if let Expr::Block(block) = &mut block_attrs{
+            for para in &cloned{
+                if let Some(subpat) = pattern::subpattern(&func.function, para){
+                    let whole = syn::Ident::new(para, Span::call_site());
+                    block.block.stmts.insert(0, syn::parse_quote!(let #whole @ #subpat = #whole;));
+                }
+            }
+        }
         // println!("{:?}", block_attrs);
         for para in &mut_para1{
             // block_attrs = block_attrs.replace(format!("* {}", para).as_str(), format!(" {} ", para).as_str());
@@ -1190,19 +1558,119 @@ pub(crate) fn generate(
         // let block_attrs = syn::parse_str::<Expr>(block_attrs.as_str()).expect("function body does not pass compiler");
         // println!("{:?}", &block_attrs);
 
-        let second_run_body = new_function_body_with_index(second_run_index, ret_ty.clone(), block_attrs);
+        let second_run_body = if borrowed::returns_reference(&func.function){
+            let ret = syn::Ident::new(&format!("ret{}", second_run_index), Span::call_site());
+            let block = match block_attrs{
+                Expr::Block(block) => block.block,
+                _ => unreachable!("the function body is a block"),
+            };
+            borrowed::run_once(&ret, &ret_ty, &block, &[])
+        }
+        else{
+            new_function_body_with_index(second_run_index, ret_ty.clone(), block_attrs)
+        };
         // println!("{}", second_run_body);
 
+        // bindings renamed for this run: the transformed variable, all cloned
+        // mutable parameters, `ret` and the `old()` snapshots
+        let renames = run_renames(key, &mut_para1, &olds, second_run_index);
+
+        // re-evaluate the `old()` expressions against the inputs of this run,
+        // nested ones refer to the snapshots of this run bound before them
+        let mut olds_for_run = TokenStream::new();
+        for old in &olds{
+            let span = old.expr.span();
+            let name = syn::Ident::new(&format!("{}_{}", old.name, second_run_index), span);
+            let expr = rename_bindings(&old.expr, &renames);
+            olds_for_run.extend(quote::quote_spanned! { span=>
+                let #name = #expr;
+            });
+        }
+
+        // re-check invariants before and post-conditions/invariants after this run
+        let run_assertions = |ctypes: &[ContractType], as_type: ContractType, renames: &[(String, String)]| -> TokenStream {
+            func
+            .contracts
+            .iter()
+            .filter(|c| ctypes.contains(&c.ty))
+            .flat_map(|c| {
+                let desc = if let Some(desc) = c.desc.as_ref() {
+                    format!(
+                        "{} of {} violated for extra run: {}",
+                        c.ty.message_name(),
+                        func_name,
+                        desc
+                    )
+                } else {
+                    format!("{} of {} violated for extra run", c.ty.message_name(), func_name)
+                };
+
+                c.assertions.iter().zip(c.streams.iter()).map(
+                    move |(expr, display)| {
+                        let mode = c.mode.final_mode();
+                        let ex = rename_bindings(expr, renames);
+                        make_assertion(
+                            mode,
+                            as_type,
+                            display.clone(),
+                            &ex,
+                            &desc,
+                        )
+                    },
+                )
+            })
+            .collect()
+        };
+        let invforclone = run_assertions(&[ContractType::Invariant], ContractType::Requires, &renames);
+        let postforclone = run_assertions(&[ContractType::Ensures, ContractType::Invariant], ContractType::Ensures, &renames);
+
+        let reseed = mr_seed::reseed(&seeded);
+
+        // the owned input of a borrowed parameter is borrowed for this run
+        let mut reborrow = match borrowed::referent(&func.function, key){
+            Some(referent) => {
+                let target = syn::Ident::new(&format!("{}{}{}", key, "_contract_", second_run_index), Span::call_site());
+                borrowed::reborrow(&target, &referent)
+            },
+            None => TokenStream::new(),
+        };
+        // the clones of references are borrowed the same way if the body may
+        // return them
+        for para in &cloned{
+            if !borrowed::returns_reference(&func.function){
+                break;
+            }
+            if let Some(by) = borrowed::clone_borrow(&func.function, para){
+                let target = syn::Ident::new(&format!("{}{}{}", para, "_contract_", second_run_index), Span::call_site());
+                reborrow.extend(borrowed::borrow_clone(&target, &by));
+            }
+        }
+
+        // a borrowed result is snapshotted before the next run starts
+        let snapshot = borrowed::snapshot(&func.function, &syn::Ident::new(&format!("ret{}", second_run_index), Span::call_site()));
+
         let new_body:TokenStream = quote::quote! {
 
                 #clone_last
 
+                #reseed
+
+                #reborrow
+
+                #olds_for_run
+
                 #preforclone
 
+                #invforclone
+
                 #second_run_body
+
+                #snapshot
+
+                #postforclone
         };
         // println!("{:?}", new_body);
-        extra_body.extend(new_body);
+        runs_body.extend(new_body);
         };
         match mr_info.mr {
             ContractType::Homomorphism | ContractType::Mapping => {
@@ -1211,6 +1679,23 @@ pub(crate) fn generate(
             },
             _ => one_extra_run(second_run_index),
         };
+
+        // a guarded relation runs and checks in one go if its inputs exist
+        match guarded_mr.get(&i){
+            Some(assertion) => {
+                let inputs = syn::Ident::new(format!("{}{}", "__contract_inputs_", i).as_str(), Span::call_site());
+                let names = &guarded_inputs[&i];
+                extra_body.extend(quote::quote! {
+                    if let Some(__contract_inputs) = #inputs {
+                        #[allow(unused_mut, unused_variables)]
+                        let (#(mut #names,)*) = __contract_inputs;
+                        #runs_body
+                        #assertion
+                    }
+                });
+            },
+            None => extra_body.extend(runs_body),
+        }
     }
 
     let mut ret_pack:TokenStream = quote::quote! {ret};
@@ -1229,7 +1714,7 @@ pub(crate) fn generate(
         {
             #pre
 
-            #olds
+            #old_bindings
 
             #body
 
@@ -1245,14 +1730,29 @@ pub(crate) fn generate(
     //     println!("{}", ret == ret1 + ret2);
     // };
 
+    // the source run keeps the generators of the caller, the follow-up runs
+    // get theirs from a copy taken before it
+    let seeding = if run_map.is_empty(){
+        TokenStream::new()
+    }
+    else{
+        mr_seed::snapshot(&func.function, &seeded)
+    };
+
+    // the relation files are included once, outside of the body that the
+    // follow-up runs copy
+    let relation_files = mr_file::include(&func.files);
+
     let new_block:TokenStream = quote::quote! {
 
         {
-            #olds
+            #relation_files
 
-            #clone_variable
+            #seeding
+
+            #old_bindings
 
-            #clone_mut
+            #clone_variable
 
             #perpare_and_body
 
@@ -1281,6 +1781,105 @@ pub(crate) fn generate(
     func.function.into_token_stream()
 }
 
+/// Compare the results of a relation up to `tolerance` instead of exactly.
+pub(crate) fn within_tolerance(assertion: TokenStream, tolerance: Option<&Expr>) -> TokenStream {
+    let tolerance = match tolerance{
+        Some(tolerance) => tolerance,
+        None => return assertion,
+    };
+    match syn::parse2::<Expr>(assertion.clone()){
+        Ok(Expr::Binary(syn::ExprBinary { left, op: syn::BinOp::Eq(_), right, .. })) => quote::quote! {
+            ((#left) - (#right)).abs() <= #tolerance
+        },
+        Ok(Expr::Binary(syn::ExprBinary { left, op: syn::BinOp::Ne(_), right, .. })) => quote::quote! {
+            ((#left) - (#right)).abs() > #tolerance
+        },
+        _ => assertion,
+    }
+}
+
+/// Apply the `on` selector of a relation to the return value `ret`.
+///
+/// The selector is either a closure that is called with a reference to the
+/// return value or an expression in terms of `ret`.
+pub(crate) fn select_on(on: &Expr, ret: &Ident) -> TokenStream {
+    match on{
+        // the closure is passed through a function so that its argument type
+        // is inferred from the bound
+        Expr::Closure(_) => quote::quote! {
+            {
+                fn __contract_on<T, R>(value: &T, on: impl Fn(&T) -> R) -> R {
+                    on(value)
+                }
+                __contract_on(&#ret, #on)
+            }
+        },
+        _ => {
+            let on = rename_bindings(on, &[(String::from("ret"), ret.to_string())]);
+            quote::quote! {
+                (#on).to_owned()
+            }
+        }
+    }
+}
+
+/// Collect the names bound by the `let` statements in `stmts`.
+fn bound_names(stmts: &TokenStream) -> Vec<Ident> {
+    let block: syn::Block = syn::parse2(quote::quote! { { #stmts } }).expect("follow-up inputs do not parse");
+    let mut names: Vec<Ident> = vec![];
+    for stmt in &block.stmts{
+        if let syn::Stmt::Local(local) = stmt{
+            if let syn::Pat::Ident(pat) = &local.pat{
+                if !names.contains(&pat.ident){
+                    names.push(pat.ident.clone());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Split the transformed variable of a relation into its root variable and
+/// an optional field path, e.g. `self.angle` into `self` and `.angle`.
+fn split_field_path(para: &TokenStream) -> (String, Option<TokenStream>) {
+    let mut expr = match syn::parse2::<Expr>(para.clone()){
+        Ok(expr) => expr,
+        Err(_) => return (para.to_string(), None),
+    };
+    let mut members = vec![];
+    while let Expr::Field(field) = expr{
+        members.push(field.member);
+        expr = *field.base;
+    }
+    if members.is_empty(){
+        return (para.to_string(), None);
+    }
+    members.reverse();
+    let field = quote::quote! { #(.#members)* };
+    (expr.to_token_stream().to_string(), Some(field))
+}
+
+/// Bind `target` to a clone of `root` in which `field` is replaced.
+///
+/// `make` computes the new field value from the field of `source`, which it
+/// receives as its first identifier, and binds it to the second identifier.
+fn field_statement(root: &Ident, field: &TokenStream, source: &Ident, target: &Ident,
+    make: &dyn Fn(&Ident, &Ident, &TokenStream) -> TokenStream) -> TokenStream {
+    let span = Span::call_site();
+    let field_old = syn::Ident::new("__contract_field", span);
+    let field_new = syn::Ident::new("__contract_field_new", span);
+    let statements = make(&field_old, &field_new, &TokenStream::new());
+    quote::quote! {
+        #[allow(unused_mut)]
+        let mut #target = #root.clone();
+        #target #field = {
+            let #field_old = #source #field.clone();
+            #statements
+            #field_new
+        };
+    }
+}
+
 fn new_function_body_with_index(index: usize, ret_ty: TokenStream, block_attrs: Expr) -> TokenStream{
     let span = Span::call_site();
     let run1 = syn::Ident::new(format!("{}{}", "run", index).as_str(), span);
@@ -1296,6 +1895,9 @@ fn new_function_body_with_index(index: usize, ret_ty: TokenStream, block_attrs:
 
 fn merge_statement(ident_type: &str , para: &syn::Ident, modi: &TokenStream, 
     op: &TokenStream, mut_def: &TokenStream, para_clone: &syn::Ident) -> TokenStream {
+    if let Some(binding) = text::follow_up(ident_type, para, modi, op, mut_def, para_clone){
+        return binding;
+    }
     let op_tokenstream:proc_macro2::TokenStream = syn::parse2(op.clone()).expect("operator not loaded correctly");
     let mut op_type = syn::parse_str("+").unwrap();
     for token in op_tokenstream{
@@ -1308,11 +1910,6 @@ fn merge_statement(ident_type: &str , para: &syn::Ident, modi: &TokenStream,
         };
     }
     match (ident_type, op_type){
-        ("str", proc_macro2::TokenTree::Punct(_)) => quote::quote! {
-            // (#para.to_string().push_str(#modi)).as_str()
-            let #mut_def #para_clone = format!("{}{}", #para.to_string(),#modi.to_string());
-            let #mut_def #para_clone = #para_clone.as_str();
-        },
         ("String",proc_macro2::TokenTree::Punct(_)) => {
             quote::quote! {
                 let #mut_def #para_clone = #para.clone()#op#modi;
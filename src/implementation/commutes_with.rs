@@ -0,0 +1,248 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::{Expr, FnArg, Ident, ItemFn, Type};
+
+use crate::implementation::{
+    codegen, equivalent_to, pattern, Contract, ContractMode, ContractType,
+    FuncWithContracts,
+};
+
+pub(crate) fn commutes_with(
+    mode: ContractMode,
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let ty = ContractType::CommutesWith;
+
+    let func = syn::parse_quote!(#toks);
+
+    let f = FuncWithContracts::new_with_initial_contract(func, ty, mode, attr);
+
+    f.generate()
+}
+
+/// Generate the check that calling the method and then the other method
+/// leaves `self` in the same state as calling them the other way around.
+///
+/// Both orders run on clones of `self` before the method body runs. The body
+/// of the method is inlined on the clones, so that its own contracts are not
+/// checked again. The other method is called normally, unless it is the
+/// method itself, in which case its body is inlined as well.
+///
+/// A method that the other method calls back into, because both commute with
+/// each other, skips the check while its own check is running, so the calls
+/// don't recurse endlessly.
+pub(crate) fn sequences(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+) -> TokenStream {
+    match try_sequences(contract, index, func) {
+        Ok(toks) => toks,
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+fn try_sequences(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+) -> Result<TokenStream, syn::Error> {
+    let mode = contract.mode.final_mode();
+
+    let cfg = match equivalent_to::mode_cfg(mode) {
+        Some(cfg) => cfg,
+        None => return Ok(TokenStream::new()),
+    };
+
+    let takes_mut_self = match func.sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) => {
+            receiver.reference.is_some() && receiver.mutability.is_some()
+        }
+        _ => false,
+    };
+
+    if !takes_mut_self {
+        let msg = "#[commutes_with] only works on methods taking `&mut self`";
+        return Err(syn::Error::new_spanned(&func.sig, msg));
+    }
+
+    let other: Ident = match contract.streams.first() {
+        Some(other) => syn::parse2(other.clone())?,
+        None => {
+            let msg = "#[commutes_with] needs the name of the other method";
+            return Err(syn::Error::new(Span::call_site(), msg));
+        }
+    };
+
+    let method = &func.sig.ident;
+    let func_name = method.to_string();
+
+    let span = Span::call_site();
+    let ident = |name: String| Ident::new(&name, span);
+
+    let other_args = &contract.streams[1..];
+    let arg_names = (0..other_args.len())
+        .map(|arg| ident(format!("__contract_commute_{}_arg_{}", index, arg)))
+        .collect::<Vec<_>>();
+
+    // the arguments of the original call
+    let own_args = func
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Typed(pat_type) => Some(pat_type),
+            FnArg::Receiver(_) => None,
+        })
+        .map(|pat_type| {
+            let name = &pat_type.pat;
+            let arg = match &*pat_type.ty {
+                Type::Reference(ty) if ty.mutability.is_some() => {
+                    quote::quote! {
+                        &mut ::std::borrow::ToOwned::to_owned(&*#name)
+                    }
+                }
+                Type::Reference(_) => name.to_token_stream(),
+                // put together from the names the pattern binds
+                _ => pattern::cloned(name).unwrap_or_else(
+                    || quote::quote!(::std::clone::Clone::clone(&#name)),
+                ),
+            };
+            (pat_type, arg)
+        })
+        .collect::<Vec<_>>();
+
+    let block = syn::parse2::<Expr>(func.block.to_token_stream())?;
+
+    // run the body of the method on `target` with the given arguments
+    let inline = |target: &Ident, args: &[TokenStream]| {
+        let body = codegen::rename_bindings(
+            &block,
+            &[(String::from("self"), target.to_string())],
+        );
+        let pats = own_args.iter().map(|(pat_type, _)| &pat_type.pat);
+
+        quote::quote! {
+            {
+                #(let #pats = #args;)*
+                #[allow(unused_mut)]
+                let mut run = || #body;
+                let _ = run();
+            }
+        }
+    };
+
+    let own_call = own_args
+        .iter()
+        .map(|(_, arg)| arg.clone())
+        .collect::<Vec<_>>();
+
+    let other_call = |target: &Ident| {
+        if other == *method {
+            let args = arg_names
+                .iter()
+                .map(|name| name.to_token_stream())
+                .collect::<Vec<_>>();
+
+            inline(target, &args)
+        } else {
+            quote::quote! {
+                let _ = #target.#other(#(#arg_names),*);
+            }
+        }
+    };
+
+    let first = ident(format!("__contract_commute_{}_first", index));
+    let second = ident(format!("__contract_commute_{}_second", index));
+
+    let run_first = inline(&first, &own_call);
+    let other_first = other_call(&first);
+    let other_second = other_call(&second);
+    let run_second = inline(&second, &own_call);
+
+    // the compared states
+    let state = |target: &Ident| match contract.option("output") {
+        Some(output) => codegen::rename_bindings(
+            output,
+            &[(String::from("self"), target.to_string())],
+        )
+        .into_token_stream(),
+        None => target.into_token_stream(),
+    };
+    let state_first = state(&first);
+    let state_second = state(&second);
+
+    let own_display = own_args
+        .iter()
+        .map(|(pat_type, _)| pat_type.pat.to_token_stream().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let other_display = other_args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let message = format!(
+        "{}: `{}({})` then `{}({})` gives {{:?}}, but the other order gives \
+         {{:?}}",
+        equivalent_to::escape(&equivalent_to::violation(contract, &func_name)),
+        func_name,
+        equivalent_to::escape(&own_display),
+        other,
+        equivalent_to::escape(&other_display),
+    );
+
+    let expected = quote::quote!((#state_first));
+    let actual = quote::quote!((#state_second));
+    let equal = equivalent_to::equality(contract, &actual, &expected);
+    let assertion =
+        equivalent_to::assertion(mode, &equal, &message, &expected, &actual);
+
+    Ok(quote::quote! {
+        #cfg
+        {
+            thread_local! {
+                static __CONTRACT_COMMUTING: ::std::cell::Cell<bool> =
+                    ::std::cell::Cell::new(false);
+            }
+
+            // resets the flag when the check is done, even if it fails
+            struct __ContractCommuting;
+
+            impl ::std::ops::Drop for __ContractCommuting {
+                fn drop(&mut self) {
+                    __CONTRACT_COMMUTING.with(|running| running.set(false));
+                }
+            }
+
+            if !__CONTRACT_COMMUTING.with(|running| running.replace(true)) {
+                let _running = __ContractCommuting;
+
+                #[allow(unused_mut)]
+                let mut #first = ::std::clone::Clone::clone(&*self);
+                #[allow(unused_mut)]
+                let mut #second = ::std::clone::Clone::clone(&*self);
+
+                {
+                    let (#(#arg_names,)*) = (#(#other_args,)*);
+                    #run_first
+                    #other_first
+                }
+
+                {
+                    let (#(#arg_names,)*) = (#(#other_args,)*);
+                    #other_second
+                    #run_second
+                }
+
+                #assertion
+            }
+        }
+    })
+}
@@ -0,0 +1,184 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::{FnArg, Ident, ItemFn, ReturnType, Type};
+
+use crate::implementation::{
+    equivalent_to, pattern, Contract, ContractMode, ContractType,
+    FuncWithContracts,
+};
+
+/// Number of threads if the relation does not name it.
+const THREADS: usize = 4;
+
+pub(crate) fn concurrent_consistency(
+    mode: ContractMode,
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let ty = ContractType::ConcurrentConsistency;
+
+    let func = syn::parse_quote!(#toks);
+
+    let f = FuncWithContracts::new_with_initial_contract(func, ty, mode, attr);
+
+    f.generate()
+}
+
+/// The number of threads, as written in the relation.
+pub(crate) fn threads(contract: &Contract) -> TokenStream {
+    match contract.option("threads") {
+        Some(threads) => threads.to_token_stream(),
+        None => quote::quote!(#THREADS),
+    }
+}
+
+/// Generate the threaded runs of the method, which are placed before the
+/// method body, and the comparisons with the result of the body, which are
+/// placed after it.
+///
+/// Every thread runs the inlined body on the shared receiver, with its own
+/// clones of the other arguments.
+pub(crate) fn parallel_runs(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+) -> (TokenStream, TokenStream) {
+    match try_parallel_runs(contract, index, func) {
+        Ok(result) => result,
+        Err(err) => (err.to_compile_error(), TokenStream::new()),
+    }
+}
+
+fn try_parallel_runs(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+) -> Result<(TokenStream, TokenStream), syn::Error> {
+    let mode = contract.mode.final_mode();
+
+    let cfg = match equivalent_to::mode_cfg(mode) {
+        Some(cfg) => cfg,
+        None => return Ok((TokenStream::new(), TokenStream::new())),
+    };
+
+    let takes_shared_self = match func.sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) => {
+            receiver.reference.is_some() && receiver.mutability.is_none()
+        }
+        _ => false,
+    };
+
+    if !takes_shared_self {
+        let msg =
+            "#[concurrent_consistency] only works on methods taking `&self`";
+        return Err(syn::Error::new_spanned(&func.sig, msg));
+    }
+
+    let func_name = func.sig.ident.to_string();
+
+    let state_ident = |name: String| {
+        let name = format!("__contract_concurrent_{}_{}", index, name);
+        Ident::new(&name, Span::call_site())
+    };
+
+    // the arguments are cloned on the calling thread and moved into the
+    // spawned one, where they are bound to the parameters again
+    let mut owned = vec![];
+    let mut rebound = vec![];
+    let mut pats = vec![];
+
+    for (idx, input) in func.sig.inputs.iter().skip(1).enumerate() {
+        if let FnArg::Typed(pat_type) = input {
+            let name = &pat_type.pat;
+            let arg = state_ident(format!("arg_{}", idx));
+
+            let (value, binding) = match &*pat_type.ty {
+                Type::Reference(ty) if ty.mutability.is_some() => (
+                    quote::quote!(::std::borrow::ToOwned::to_owned(&*#name)),
+                    quote::quote!(&mut #arg),
+                ),
+                Type::Reference(_) => {
+                    (name.to_token_stream(), arg.to_token_stream())
+                }
+                // put together from the names the pattern binds
+                _ => (
+                    pattern::cloned(name).unwrap_or_else(
+                        || quote::quote!(::std::clone::Clone::clone(&#name)),
+                    ),
+                    arg.to_token_stream(),
+                ),
+            };
+
+            owned.push((arg, value));
+            rebound.push(binding);
+            pats.push(name);
+        }
+    }
+
+    let ret_ty = match &func.sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::ImplTrait(_) => TokenStream::new(),
+            ty => quote::quote!(-> #ty),
+        },
+        ReturnType::Default => TokenStream::new(),
+    };
+
+    let block = &func.block;
+    let threads = threads(contract);
+    let rets = state_ident(String::from("rets"));
+    let (args, values): (Vec<_>, Vec<_>) = owned.into_iter().unzip();
+
+    let call = quote::quote! {
+        #cfg
+        let #rets = ::std::thread::scope(|scope| {
+            let handles = (0..#threads)
+                .map(|_| {
+                    #(#[allow(unused_mut)] let mut #args = #values;)*
+                    scope.spawn(move || {
+                        #(let #pats = #rebound;)*
+                        #[allow(unused_mut)]
+                        let mut run = || #ret_ty #block;
+                        run()
+                    })
+                })
+                .collect::<::std::vec::Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| ::std::panic::resume_unwind(err))
+                })
+                .collect::<::std::vec::Vec<_>>()
+        });
+    };
+
+    let message = format!(
+        "{}: `{}` returned {{:?}} when called alone, but {{:?}} when called \
+         from {} threads at once",
+        equivalent_to::escape(&equivalent_to::violation(contract, &func_name)),
+        func_name,
+        equivalent_to::escape(&threads.to_string()),
+    );
+
+    let thread_ret = state_ident(String::from("ret"));
+    let expected = quote::quote!(ret);
+    let actual = thread_ret.to_token_stream();
+    let equal = equivalent_to::equality(contract, &actual, &expected);
+    let assertion =
+        equivalent_to::assertion(mode, &equal, &message, &expected, &actual);
+
+    let checks = quote::quote! {
+        #cfg
+        for #thread_ret in #rets {
+            #assertion
+        }
+    };
+
+    Ok((call, checks))
+}
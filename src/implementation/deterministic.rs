@@ -0,0 +1,123 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::implementation::{
+    metamorphic, mr_seed, pattern, Contract, ContractMode, ContractType,
+};
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::{Expr, ExprLit, FnArg, Ident, ItemFn, Lit, Pat, Type};
+
+/// Name of the variable that stands in for the input of a function without
+/// parameters, whose second run has nothing to clone.
+pub(crate) const UNIT: &str = "__contract_unit";
+
+pub(crate) fn deterministic(
+    mode: ContractMode,
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let ty = ContractType::Deterministic;
+
+    metamorphic::relation(ty, mode, attr, toks)
+}
+
+/// Clone the first parameter of `func` for the second run, unless the
+/// relation names the variable itself.
+///
+/// The identity transformation works on any of the parameters, the others are
+/// passed to the second run just like for the other relations. A function
+/// without parameters is run again as it is, with [`UNIT`] as its input.
+pub(crate) fn default_input(contract: &mut Contract, func: &ItemFn) {
+    if contract.ty != ContractType::Deterministic
+        || !contract.streams.is_empty()
+    {
+        return;
+    }
+
+    // a random number generator is copied for the second run anyway, so it
+    // is only the cloned variable if there is nothing else
+    let generics = &func.sig.generics;
+    let inputs = || {
+        func.sig.inputs.iter().filter_map(|input| match input {
+            FnArg::Receiver(receiver) => {
+                Some((receiver.self_token.into_token_stream(), false))
+            }
+            // a pattern is represented by the first name it binds
+            FnArg::Typed(pat_type) => pattern::bindings(pat_type)
+                .into_iter()
+                .next()
+                .map(|binding| {
+                    (
+                        binding.ident.into_token_stream(),
+                        mr_seed::is_seeded(pat_type, generics),
+                    )
+                }),
+        })
+    };
+
+    let first = inputs()
+        .find(|(_, seeded)| !seeded)
+        .or_else(|| inputs().next())
+        .map(|(input, _)| input);
+
+    let input = first.unwrap_or_else(|| {
+        Ident::new(UNIT, Span::call_site()).into_token_stream()
+    });
+
+    contract.streams.push(input);
+}
+
+/// The parameters whose values after both runs are compared, as the name of
+/// the parameter and the value it refers to.
+///
+/// These are `&mut self` and the `&mut` parameters, if the relation is given
+/// `mutated = true`.
+pub(crate) fn mutated_inputs(
+    contract: &Contract,
+    func: &ItemFn,
+) -> Vec<(String, TokenStream)> {
+    if !compares_mutated(contract) {
+        return vec![];
+    }
+
+    func.sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Receiver(receiver)
+                if receiver.reference.is_some()
+                    && receiver.mutability.is_some() =>
+            {
+                Some((String::from("self"), quote::quote!(*self)))
+            }
+            FnArg::Typed(pat_type) => match (&*pat_type.pat, &*pat_type.ty) {
+                (Pat::Ident(pat), Type::Reference(ty))
+                    if ty.mutability.is_some() =>
+                {
+                    let ident = &pat.ident;
+                    Some((ident.to_string(), quote::quote!(*#ident)))
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether the relation is `#[deterministic]` and given `mutated = true`.
+pub(crate) fn compares_mutated(contract: &Contract) -> bool {
+    if contract.ty != ContractType::Deterministic {
+        return false;
+    }
+
+    match contract.option("mutated") {
+        Some(Expr::Lit(ExprLit {
+            lit: Lit::Bool(enabled),
+            ..
+        })) => enabled.value,
+        Some(_) => true,
+        None => false,
+    }
+}
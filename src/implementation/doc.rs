@@ -2,12 +2,19 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::implementation::{Contract, ContractMode};
+use crate::implementation::{
+    chain, concurrent_consistency, deterministic, statistical, Contract,
+    ContractMode, ContractType,
+};
 use proc_macro2::Span;
-use proc_macro2::TokenStream;
-use syn::{parse::Parser, Attribute};
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{parse::Parser, Attribute, FnArg, ItemFn, Pat};
 
-pub(crate) fn generate_attributes(contracts: &[Contract]) -> Vec<Attribute> {
+pub(crate) fn generate_attributes(
+    contracts: &[Contract],
+    func: &ItemFn,
+) -> Vec<Attribute> {
     let mut attrs = vec![];
 
     fn make_attribute(content: &str) -> Attribute {
@@ -29,10 +36,7 @@ pub(crate) fn generate_attributes(contracts: &[Contract]) -> Vec<Attribute> {
         stream.to_string()
     }
 
-    // header
-    attrs.push(make_attribute("# Contracts"));
-
-    for contract in contracts {
+    fn document_contract(attrs: &mut Vec<Attribute>, contract: &Contract) {
         let ty = contract.ty;
         let mode = match contract.mode {
             ContractMode::Always => None,
@@ -42,6 +46,36 @@ pub(crate) fn generate_attributes(contracts: &[Contract]) -> Vec<Attribute> {
             ContractMode::LogOnly => None,
         };
 
+        // the arguments of the other method belong to its call
+        let streams = match ty {
+            ContractType::CommutesWith => {
+                let other = contract.streams.first().map(print_stream);
+                let args = contract
+                    .streams
+                    .iter()
+                    .skip(1)
+                    .map(print_stream)
+                    .collect::<Vec<_>>();
+
+                vec![format!(
+                    "{}({})",
+                    other.unwrap_or_default(),
+                    args.join(", ")
+                )]
+            }
+            ContractType::ConcurrentConsistency => vec![format!(
+                "{} threads",
+                concurrent_consistency::threads(contract)
+            )],
+            ContractType::Statistical => vec![format!(
+                "{} of {} and {}",
+                statistical::relation(contract),
+                print_stream(&contract.streams[0]),
+                statistical::follow_up(contract)
+            )],
+            _ => contract.streams.iter().map(print_stream).collect(),
+        };
+
         if let Some(desc) = &contract.desc {
             // document all assertions under the description
 
@@ -53,29 +87,19 @@ pub(crate) fn generate_attributes(contracts: &[Contract]) -> Vec<Attribute> {
 
             attrs.push(make_attribute(&header_txt));
 
-            for (_assert, stream) in
-                contract.assertions.iter().zip(contract.streams.iter())
-            {
-                attrs.push(make_attribute(&format!(
-                    " - `{}`",
-                    print_stream(stream)
-                )));
+            for stream in &streams {
+                attrs.push(make_attribute(&format!(" - `{}`", stream)));
             }
 
             attrs.push(make_attribute(""));
         } else {
             // document each assertion on its own
 
-            for stream in &contract.streams {
+            for stream in &streams {
                 let doc_str = if let Some(name) = mode {
-                    format!(
-                        "{} - {}: `{}`",
-                        ty.message_name(),
-                        name,
-                        print_stream(stream)
-                    )
+                    format!("{} - {}: `{}`", ty.message_name(), name, stream)
                 } else {
-                    format!("{}: `{}`", ty.message_name(), print_stream(stream))
+                    format!("{}: `{}`", ty.message_name(), stream)
                 };
 
                 attrs.push(make_attribute(&doc_str));
@@ -84,5 +108,524 @@ pub(crate) fn generate_attributes(contracts: &[Contract]) -> Vec<Attribute> {
         }
     }
 
+    let (relations, checks): (Vec<&Contract>, Vec<&Contract>) =
+        contracts.iter().partition(|c| c.ty.is_metamorphic());
+
+    // contracts that only log or are not checked at all don't panic
+    let (panics, others): (Vec<&Contract>, Vec<&Contract>) =
+        checks.into_iter().partition(|c| {
+            !matches!(
+                c.mode.final_mode(),
+                ContractMode::LogOnly | ContractMode::Disabled
+            )
+        });
+
+    if !panics.is_empty() {
+        attrs.push(make_attribute("# Panics"));
+
+        for contract in panics {
+            document_contract(&mut attrs, contract);
+        }
+    }
+
+    if !others.is_empty() {
+        attrs.push(make_attribute("# Contracts"));
+
+        for contract in others {
+            document_contract(&mut attrs, contract);
+        }
+    }
+
+    if !relations.is_empty() {
+        attrs.push(make_attribute("# Metamorphic Relations"));
+
+        let signature = Signature::new(func);
+
+        for contract in relations {
+            let name = contract.ty.message_name();
+            let relation = format!(
+                "`{}`{}",
+                signature.relation(contract),
+                relation_options(contract)
+            );
+
+            if let Some(desc) = &contract.desc {
+                attrs.push(make_attribute(&format!("{}: {}", name, desc)));
+                attrs.push(make_attribute(&format!(" - {}", relation)));
+            } else {
+                attrs.push(make_attribute(&format!("{}: {}", name, relation)));
+            }
+
+            attrs.push(make_attribute(""));
+        }
+    }
+
     attrs
 }
+
+/// The parts of a function signature that are needed to write down calls of
+/// the function.
+struct Signature {
+    name: String,
+    receiver: bool,
+    /// Names and (last token of the) types of the parameters.
+    params: Vec<(String, String)>,
+}
+
+impl Signature {
+    fn new(func: &ItemFn) -> Self {
+        let mut receiver = false;
+        let mut params = vec![];
+
+        for input in &func.sig.inputs {
+            match input {
+                FnArg::Receiver(_) => receiver = true,
+                FnArg::Typed(pat_type) => {
+                    let name = match &*pat_type.pat {
+                        Pat::Ident(pat) => pat.ident.to_string(),
+                        pat => tidy(&pat.to_token_stream()),
+                    };
+                    let ty = pat_type.ty.to_token_stream().to_string();
+                    let ty = ty
+                        .split(' ')
+                        .next_back()
+                        .unwrap_or_default()
+                        .to_string();
+
+                    params.push((name, ty));
+                }
+            }
+        }
+
+        Self {
+            name: func.sig.ident.to_string(),
+            receiver,
+            params,
+        }
+    }
+
+    /// Write down a call where the transformed variable `key` has `value`.
+    fn call(&self, key: &str, value: &str) -> String {
+        let is_param = self.params.iter().any(|(name, _)| name == key);
+
+        let args = self
+            .params
+            .iter()
+            .map(|(name, _)| {
+                if is_param && name == key {
+                    value.to_string()
+                } else {
+                    name.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let call = if self.receiver {
+            format!("self.{}({})", self.name, args)
+        } else {
+            format!("{}({})", self.name, args)
+        };
+
+        // `self` and fields of variables are substituted in the call
+        if is_param || key == value {
+            call
+        } else {
+            format!("{}[{} := {}]", call, key, value)
+        }
+    }
+
+    /// Instantiate the formula of a relation with the parameters of the
+    /// function, mirroring the checks done for the follow-up runs.
+    fn relation(&self, contract: &Contract) -> String {
+        let arg =
+            |idx: usize| contract.streams.get(idx).cloned().unwrap_or_default();
+
+        let key = tidy(&arg(0));
+        let (op, modi) = (arg(1), tidy(&arg(2)));
+        let (extra_op, extra_modi) = (arg(3), tidy(&arg(4)));
+
+        let f = |value: &str| self.call(&key, value);
+        // a chain of transformations replaces the operator and the modifier
+        let chained = |value: &str| chain::describe(contract, value);
+        let moved =
+            chained(&key).unwrap_or_else(|| transform(&key, &op, &modi));
+        // and a chain compared step by step compares with the step before
+        let source =
+            chain::source(contract, &key).unwrap_or_else(|| key.clone());
+
+        match contract.ty {
+            ContractType::Periodicity => {
+                format!("{} == {}", f(&moved), f(&source))
+            }
+            ContractType::AddNotEqual => {
+                format!("{} != {}", f(&moved), f(&source))
+            }
+            ContractType::DimensionTrans => {
+                let extra_modi = if extra_modi.is_empty() {
+                    modi
+                } else {
+                    extra_modi
+                };
+
+                format!(
+                    "{} == {}",
+                    f(&moved),
+                    transform(&f(&source), &extra_op, &extra_modi)
+                )
+            }
+            ContractType::Monotonicity => {
+                transform(&f(&source), &extra_op, &f(&moved))
+            }
+            ContractType::Symmetry => {
+                let ty = self
+                    .params
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map_or("", |(_, ty)| ty.as_str());
+                let mirrored = chained(&key)
+                    .unwrap_or_else(|| mirror(&key, ty, &op, &modi));
+                let sign = if tidy(&extra_op) == "-" { "-" } else { "" };
+
+                format!("{} == {}{}", f(&source), sign, f(&mirrored))
+            }
+            ContractType::Homomorphism => format!(
+                "{} == {}",
+                transform(&f(&key), &op, &f(&modi)),
+                f(&moved)
+            ),
+            ContractType::IterConsistency => format!(
+                "{} == {}",
+                transform(&f(&f(&key)), &op, &f(&key)),
+                transform(&f(&key), &op, &key)
+            ),
+            ContractType::Mapping => {
+                let moved_twice = chained(&moved)
+                    .unwrap_or_else(|| transform(&moved, &op, &modi));
+
+                format!(
+                    "{} == {}",
+                    transform(&f(&moved), &extra_op, &f(&key)),
+                    transform(&f(&moved_twice), &extra_op, &f(&moved))
+                )
+            }
+            ContractType::Deterministic => {
+                format!("{} == {}", f(&key), f(&key))
+            }
+            ContractType::Inclusion => {
+                // a method call changes the input in place
+                let changed = match op.clone().into_iter().next() {
+                    _ if contract.option("transform").is_some() => moved,
+                    Some(TokenTree::Punct(_)) => {
+                        format!("{} {}", key, tidy(&op))
+                    }
+                    _ => format!("{}.{}", key, tidy(&op)),
+                };
+                let symbol = if modi == "Subset" { "⊆" } else { "⊇" };
+
+                format!("{} {} {}", f(&changed), symbol, f(&source))
+            }
+            _ => unreachable!("not a metamorphic relation"),
+        }
+    }
+}
+
+/// Describe the named arguments that change what a relation compares.
+fn relation_options(contract: &Contract) -> String {
+    let mut text = String::new();
+
+    if let Some(output) = contract.option("output") {
+        text.push_str(&format!(
+            ", relating `{}` after each call",
+            tidy(&output.to_token_stream())
+        ));
+    }
+
+    if let Some(on) = contract.option("on") {
+        text.push_str(&format!(
+            ", relating `{}` of each result",
+            tidy(&on.to_token_stream())
+        ));
+    }
+
+    if deterministic::compares_mutated(contract) {
+        text.push_str(", and the `&mut` inputs after each call");
+    }
+
+    if let Some(guard) = contract.option("when") {
+        text.push_str(&format!(
+            ", if `{}` holds before the call",
+            tidy(&guard.to_token_stream())
+        ));
+    }
+
+    text
+}
+
+/// Apply the operator of a relation, which is either a binary operator, the
+/// name of a method or a method called with its modifier.
+fn transform(value: &str, op: &TokenStream, modi: &str) -> String {
+    let is_call = op
+        .clone()
+        .into_iter()
+        .any(|tt| matches!(tt, TokenTree::Group(_)));
+
+    match method_name(op) {
+        Some(_) if is_call => format!("{}.{}", parenthesize(value), tidy(op)),
+        Some(method) => format!("{}.{}({})", parenthesize(value), method, modi),
+        None => format!("{} {} {}", parenthesize(value), tidy(op), modi),
+    }
+}
+
+/// The input of the follow-up run of a symmetry, reflected at `modi`.
+fn mirror(key: &str, ty: &str, op: &TokenStream, modi: &str) -> String {
+    const INTS: &[&str] = &[
+        "i128", "i16", "i32", "i64", "i8", "isize", "u128", "u16", "u32",
+        "u64", "u8", "usize",
+    ];
+
+    if INTS.contains(&ty) {
+        format!("2 * {} {} {}", parenthesize(modi), tidy(op), key)
+    } else if ["f32", "f64"].contains(&ty) {
+        format!("2.0 * {} {} {}", parenthesize(modi), tidy(op), key)
+    } else if ty == "bool" {
+        format!("!{}", key)
+    } else if let Some(method) = method_name(op) {
+        format!("{}.{}()", key, method)
+    } else {
+        format!("{} {} {}", modi, tidy(op), key)
+    }
+}
+
+fn method_name(op: &TokenStream) -> Option<String> {
+    op.clone().into_iter().find_map(|tt| match tt {
+        TokenTree::Ident(ident) => Some(ident.to_string()),
+        _ => None,
+    })
+}
+
+/// Put composite expressions into parentheses before they are used as an
+/// operand.
+fn parenthesize(expr: &str) -> String {
+    let mut depth = 0;
+    let composite = expr.chars().any(|c| {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+        c == ' ' && depth == 0
+    });
+
+    if composite {
+        format!("({})", expr)
+    } else {
+        expr.to_string()
+    }
+}
+
+/// Print tokens closer to how they are usually written, token streams put
+/// spaces between all tokens.
+fn tidy(stream: &TokenStream) -> String {
+    let text = stream
+        .to_string()
+        .replace(" . ", ".")
+        .replace(" :: ", "::")
+        .replace("( ", "(")
+        .replace(" )", ")")
+        .replace(" ,", ",");
+
+    // calls are written without a space before the arguments
+    let mut tidied = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let prev = tidied.chars().last();
+        let is_call = c == ' '
+            && chars.peek() == Some(&'(')
+            && matches!(prev, Some(p) if p.is_alphanumeric() || p == '_' || p == '!');
+
+        if !is_call {
+            tidied.push(c);
+        }
+    }
+
+    tidied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::implementation::ContractMode;
+
+    fn relation_doc(
+        attr: TokenStream,
+        ty: ContractType,
+        func: ItemFn,
+    ) -> String {
+        let mut contract = Contract::from_toks(ty, ContractMode::Test, attr);
+        chain::make_room(&mut contract);
+        let signature = Signature::new(&func);
+
+        format!(
+            "{}{}",
+            signature.relation(&contract),
+            relation_options(&contract)
+        )
+    }
+
+    #[test]
+    fn relations_use_parameter_names() {
+        let add_book: ItemFn = syn::parse_quote! {
+            fn add_book(&mut self, book_id: usize) {}
+        };
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(book_id, +, 1),
+                ContractType::Periodicity,
+                add_book.clone()
+            ),
+            "self.add_book(book_id + 1) == self.add_book(book_id)"
+        );
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(book_id, +, 1, <=, output = self.available.len()),
+                ContractType::Monotonicity,
+                add_book
+            ),
+            "self.add_book(book_id) <= self.add_book(book_id + 1), \
+             relating `self.available.len()` after each call"
+        );
+    }
+
+    #[test]
+    fn relations_with_several_runs() {
+        let area: ItemFn = syn::parse_quote! {
+            fn area(label: &str, scale: f64) -> f64 {}
+        };
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(scale, *, 2.0, *, 4.0),
+                ContractType::DimensionTrans,
+                area.clone()
+            ),
+            "area(label, scale * 2.0) == area(label, scale) * 4.0"
+        );
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(scale, +, 1.0, -),
+                ContractType::Mapping,
+                area
+            ),
+            "area(label, scale + 1.0) - area(label, scale) == \
+             area(label, (scale + 1.0) + 1.0) - area(label, scale + 1.0)"
+        );
+    }
+
+    #[test]
+    fn relations_on_fields() {
+        let quadrant: ItemFn = syn::parse_quote! {
+            fn quadrant(&self) -> i32 {}
+        };
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(self.angle, +, 360, when = self.angle >= 0),
+                ContractType::Periodicity,
+                quadrant
+            ),
+            "self.quadrant()[self.angle := self.angle + 360] == self.quadrant(), \
+             if `self.angle >= 0` holds before the call"
+        );
+    }
+
+    #[test]
+    fn text_transformations() {
+        let normalize: ItemFn = syn::parse_quote! {
+            fn normalize(host: &str) -> String {}
+        };
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(host, pad("\t")),
+                ContractType::Periodicity,
+                normalize
+            ),
+            "normalize(host.pad(\"\\t\")) == normalize(host)"
+        );
+    }
+
+    #[test]
+    fn transform_chains() {
+        let total: ItemFn = syn::parse_quote! {
+            fn total(xs: Vec<i32>) -> i32 {}
+        };
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(xs, <=, transform = [rotate(1), push(4)]),
+                ContractType::Monotonicity,
+                total
+            ),
+            "total(xs) <= total(xs.rotate(1).push(4))"
+        );
+    }
+
+    #[test]
+    fn chains_compared_step_by_step() {
+        let total: ItemFn = syn::parse_quote! {
+            fn total(xs: Vec<i32>) -> i32 {}
+        };
+
+        let mut contract = Contract::from_toks(
+            ContractType::Monotonicity,
+            ContractMode::Test,
+            quote::quote!(xs, <=, transform = [push(4), push(5)], compare = steps),
+        );
+        chain::make_room(&mut contract);
+        let signature = Signature::new(&total);
+
+        let relations = chain::split(contract)
+            .unwrap()
+            .iter()
+            .map(|contract| signature.relation(contract))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            relations,
+            vec![
+                "total(xs) <= total(xs.push(4))",
+                "total(xs.push(4)) <= total(xs.push(4).push(5))",
+            ]
+        );
+    }
+
+    #[test]
+    fn inclusions() {
+        let search: ItemFn = syn::parse_quote! {
+            fn search(items: Vec<u32>, min: u32) -> Vec<u32> {}
+        };
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(items, push(7), Superset),
+                ContractType::Inclusion,
+                search.clone()
+            ),
+            "search(items.push(7), min) ⊇ search(items, min)"
+        );
+
+        assert_eq!(
+            relation_doc(
+                quote::quote!(min, + 1, Subset),
+                ContractType::Inclusion,
+                search
+            ),
+            "search(items, min + 1) ⊆ search(items, min)"
+        );
+    }
+}
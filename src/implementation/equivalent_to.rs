@@ -0,0 +1,331 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::{FnArg, Ident, ItemFn, Pat, PatType, Type};
+
+use crate::implementation::{
+    codegen, pattern, Contract, ContractMode, ContractType, FuncWithContracts,
+};
+
+pub(crate) fn equivalent_to(
+    mode: ContractMode,
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let ty = ContractType::EquivalentTo;
+
+    let func = syn::parse_quote!(#toks);
+
+    let f = FuncWithContracts::new_with_initial_contract(func, ty, mode, attr);
+
+    f.generate()
+}
+
+/// A value that is compared between the function and its reference
+/// implementation after both ran.
+pub(crate) struct Compared {
+    /// How the value is named in messages.
+    pub(crate) name: String,
+    /// The value as produced by the function.
+    pub(crate) actual: TokenStream,
+    /// The value as produced by the reference implementation.
+    pub(crate) expected: TokenStream,
+}
+
+/// Generate the call of the reference implementation, which is placed before
+/// the function body, and the comparisons of the results, which are placed
+/// after it.
+///
+/// The reference implementation gets the same arguments as the function.
+/// Owned arguments and `&mut` parameters are cloned for it, so it runs on the
+/// inputs before the body changed them.
+pub(crate) fn differential(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+) -> (TokenStream, TokenStream) {
+    let mode = contract.mode.final_mode();
+
+    let cfg = match mode_cfg(mode) {
+        Some(cfg) => cfg,
+        None => return (TokenStream::new(), TokenStream::new()),
+    };
+
+    let oracle = match contract.assertions.first() {
+        Some(oracle) => oracle,
+        None => {
+            let msg = "#[equivalent_to] needs the reference implementation";
+            let err = syn::Error::new(Span::call_site(), msg);
+            return (err.to_compile_error(), TokenStream::new());
+        }
+    };
+
+    let func_name = func.sig.ident.to_string();
+    let oracle_name = oracle.to_token_stream().to_string().replace(' ', "");
+
+    let state_ident = |name: &str| {
+        let name = format!("__contract_oracle_{}_{}", index, name);
+        Ident::new(&name, Span::call_site())
+    };
+
+    let mut inputs = TokenStream::new();
+    let mut args = vec![];
+    let mut compared = vec![];
+
+    for input in &func.sig.inputs {
+        match input {
+            FnArg::Receiver(receiver) => {
+                match (&receiver.reference, receiver.mutability) {
+                    (Some(_), Some(_)) => {
+                        let state = state_ident("self");
+                        inputs.extend(quote::quote! {
+                            #cfg
+                            let mut #state = ::std::clone::Clone::clone(&*self);
+                        });
+                        args.push(quote::quote!(&mut #state));
+                        compared.push(Compared {
+                            name: String::from("self"),
+                            actual: quote::quote!(*self),
+                            expected: state.into_token_stream(),
+                        });
+                    }
+                    (Some(_), None) => args.push(quote::quote!(self)),
+                    (None, _) => args
+                        .push(quote::quote!(::std::clone::Clone::clone(&self))),
+                }
+            }
+            FnArg::Typed(pat_type) => {
+                let arg = typed_argument(
+                    contract.ty,
+                    pat_type,
+                    &state_ident,
+                    &cfg,
+                    &mut inputs,
+                    &mut compared,
+                );
+
+                match arg {
+                    Ok(arg) => args.push(arg),
+                    Err(err) => {
+                        return (err.to_compile_error(), TokenStream::new())
+                    }
+                }
+            }
+        }
+    }
+
+    let ret = state_ident("ret");
+    let call = quote::quote! {
+        #inputs
+
+        #cfg
+        let #ret = (#oracle)(#(#args),*);
+    };
+
+    compared.insert(
+        0,
+        Compared {
+            name: String::from("ret"),
+            actual: quote::quote!(ret),
+            expected: ret.into_token_stream(),
+        },
+    );
+
+    let desc = escape(&violation(contract, &func_name));
+    let oracle_name = escape(&oracle_name);
+
+    let mut checks = TokenStream::new();
+
+    for Compared {
+        name,
+        actual,
+        expected,
+    } in compared
+    {
+        let message = if name == "ret" {
+            format!(
+                "{}: `{}` returned {{:?}}, but `{}` returned {{:?}}",
+                desc, oracle_name, func_name
+            )
+        } else {
+            format!(
+                "{}: `{}` is {{:?}} after `{}`, but {{:?}} after `{}`",
+                desc, name, oracle_name, func_name
+            )
+        };
+
+        let equal = equality(contract, &actual, &expected);
+
+        checks.extend(assertion(mode, &equal, &message, &expected, &actual));
+    }
+
+    let checks = quote::quote! {
+        #cfg
+        {
+            #checks
+        }
+    };
+
+    (call, checks)
+}
+
+/// The attribute that enables the checks of `mode`, or `None` if they are
+/// disabled.
+pub(crate) fn mode_cfg(mode: ContractMode) -> Option<TokenStream> {
+    match mode {
+        ContractMode::Disabled => None,
+        ContractMode::Test => Some(quote::quote!(#[cfg(test)])),
+        ContractMode::Debug => Some(quote::quote!(#[cfg(debug_assertions)])),
+        ContractMode::Always | ContractMode::LogOnly => {
+            Some(TokenStream::new())
+        }
+    }
+}
+
+/// Pass a typed parameter on to another implementation.
+///
+/// References are passed as they are, the values behind `&mut` references
+/// are cloned into a binding named by `state_ident` and compared afterwards.
+/// Owned values are cloned, as the function body consumes them.
+pub(crate) fn typed_argument(
+    ty: ContractType,
+    pat_type: &PatType,
+    state_ident: &dyn Fn(&str) -> Ident,
+    cfg: &TokenStream,
+    inputs: &mut TokenStream,
+    compared: &mut Vec<Compared>,
+) -> Result<TokenStream, syn::Error> {
+    let ident = match &*pat_type.pat {
+        Pat::Ident(pat) if pat.by_ref.is_none() => &pat.ident,
+        // an owned argument taken apart by its pattern is put together again
+        pat if !matches!(*pat_type.ty, Type::Reference(_)) => {
+            match pattern::cloned(pat) {
+                Some(value) => return Ok(value),
+                None => {
+                    let msg = format!(
+                        "#[{}] needs patterns that bind every part of the \
+                         argument",
+                        ty.message_name()
+                    );
+                    return Err(syn::Error::new_spanned(pat, msg));
+                }
+            }
+        }
+        pat => {
+            let msg =
+                format!("#[{}] needs named parameters", ty.message_name());
+            return Err(syn::Error::new_spanned(pat, msg));
+        }
+    };
+
+    let arg = match &*pat_type.ty {
+        Type::Reference(ty) if ty.mutability.is_some() => {
+            // an owned copy, so `&mut [T]` works as well
+            let state = state_ident(&ident.to_string());
+            inputs.extend(quote::quote! {
+                #cfg
+                let mut #state = ::std::borrow::ToOwned::to_owned(&*#ident);
+            });
+            compared.push(Compared {
+                name: ident.to_string(),
+                actual: quote::quote!(*#ident),
+                expected: state.to_token_stream(),
+            });
+            quote::quote!(&mut #state)
+        }
+        Type::Reference(_) => ident.into_token_stream(),
+        _ => quote::quote!(::std::clone::Clone::clone(&#ident)),
+    };
+
+    Ok(arg)
+}
+
+/// The start of the message for a violation of `contract`.
+pub(crate) fn violation(contract: &Contract, func_name: &str) -> String {
+    match &contract.desc {
+        Some(desc) => format!(
+            "{} of {} violated: {}",
+            contract.ty.message_name(),
+            func_name,
+            desc
+        ),
+        None => {
+            format!("{} of {} violated", contract.ty.message_name(), func_name)
+        }
+    }
+}
+
+/// Assert that `equal` holds, showing the two compared values in the
+/// `message` otherwise.
+pub(crate) fn assertion(
+    mode: ContractMode,
+    equal: &TokenStream,
+    message: &str,
+    expected: &TokenStream,
+    actual: &TokenStream,
+) -> TokenStream {
+    let mut result = TokenStream::new();
+
+    if mode == ContractMode::LogOnly {
+        result.extend(quote::quote! {
+            if !(#equal) {
+                log::error!(#message, #expected, #actual);
+            }
+        });
+    }
+
+    let span = Span::call_site();
+    if let Some(assert_macro) =
+        codegen::get_assert_macro(ContractType::Ensures, mode, span)
+    {
+        result.extend(quote::quote! {
+            #assert_macro!(#equal, #message, #expected, #actual);
+        });
+    }
+
+    result
+}
+
+/// Compare a value with the one of the reference implementation, using the
+/// `eq` or `tolerance` argument if given.
+pub(crate) fn equality(
+    contract: &Contract,
+    actual: &TokenStream,
+    expected: &TokenStream,
+) -> TokenStream {
+    if let Some(eq) = contract.option("eq") {
+        // closures are put in parentheses to keep their commas together
+        let eq = match eq {
+            syn::Expr::Paren(paren) => &*paren.expr,
+            eq => eq,
+        };
+
+        // the comparison is passed through a function so that the argument
+        // types of a closure are inferred from the bound
+        return quote::quote! {
+            {
+                fn __contract_eq<A: ?Sized, B: ?Sized>(
+                    a: &A,
+                    b: &B,
+                    eq: impl Fn(&A, &B) -> bool,
+                ) -> bool {
+                    eq(a, b)
+                }
+                __contract_eq(&#actual, &#expected, #eq)
+            }
+        };
+    }
+
+    codegen::within_tolerance(
+        quote::quote!(#actual == #expected),
+        contract.option("tolerance"),
+    )
+}
+
+/// Escape the braces of `text` for use in a format string.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
@@ -0,0 +1,86 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Ident, TokenStream, TokenTree};
+
+use crate::implementation::{
+    metamorphic, Contract, ContractMode, ContractType,
+};
+
+pub(crate) fn inclusion(
+    mode: ContractMode,
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let ty = ContractType::Inclusion;
+
+    metamorphic::relation(ty, mode, attr, toks)
+}
+
+/// How the result of the follow-up run relates to the original result.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Direction {
+    /// The follow-up result contains every element of the original one.
+    Superset,
+    /// The original result contains every element of the follow-up one.
+    Subset,
+}
+
+impl Direction {
+    /// The direction named by the third argument of the relation.
+    pub(crate) fn of(contract: &Contract) -> Result<Direction, syn::Error> {
+        let arg = contract.streams.get(2).cloned().unwrap_or_default();
+
+        match arg.to_string().as_str() {
+            "Superset" => Ok(Direction::Superset),
+            "Subset" => Ok(Direction::Subset),
+            _ => Err(syn::Error::new_spanned(
+                arg,
+                "#[inclusion] needs `Superset` or `Subset` as its third \
+                 argument",
+            )),
+        }
+    }
+
+    /// The relation as it is printed on a violation.
+    pub(crate) fn relation(self) -> &'static str {
+        match self {
+            Direction::Superset => "f(T(x)) ⊇ f(x)",
+            Direction::Subset => "f(T(x)) ⊆ f(x)",
+        }
+    }
+}
+
+/// Bind `target` to a clone of `source` that is changed by `transform`.
+///
+/// A transformation starting with an operator, like `+ 1`, is applied to the
+/// clone, any other is a method call like `push(4)` that changes the clone in
+/// place.
+pub(crate) fn follow_up_input(
+    source: &Ident,
+    target: &Ident,
+    transform: &TokenStream,
+    mut_def: &TokenStream,
+) -> TokenStream {
+    match transform.clone().into_iter().next() {
+        Some(TokenTree::Punct(_)) => quote::quote! {
+            let #mut_def #target = #source.clone() #transform;
+        },
+        _ => quote::quote! {
+            #[allow(unused_mut)]
+            let mut #target = #source.clone();
+            #target.#transform;
+        },
+    }
+}
+
+/// Whether every element of the collection `sub` is contained in `sup`.
+pub(crate) fn contains_all(
+    sub: &TokenStream,
+    sup: &TokenStream,
+) -> TokenStream {
+    quote::quote! {
+        #sub.iter().all(|e| #sup.contains(e))
+    }
+}
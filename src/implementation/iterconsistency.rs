@@ -2,9 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
- use crate::implementation::{ContractMode, ContractType, FuncWithContracts};
+ use crate::implementation::{metamorphic, ContractMode, ContractType};
  use proc_macro2::TokenStream;
- use syn::ItemFn;
  
  pub(crate) fn iter_consistency(
      mode: ContractMode,
@@ -13,9 +12,5 @@
  ) -> TokenStream {
      let ty = ContractType::IterConsistency;
  
-     let func: ItemFn = syn::parse_quote!(#toks);
- 
-     let f = FuncWithContracts::new_with_initial_contract(func, ty, mode, attr);
-     
-     f.generate()
+     metamorphic::relation(ty, mode, attr, toks)
  }
\ No newline at end of file
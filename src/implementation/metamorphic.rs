@@ -0,0 +1,279 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{FnArg, ImplItem, ImplItemMethod, Item, ItemFn, ItemImpl, Pat, Type};
+
+use crate::implementation::{
+    parse, pattern, ContractMode, ContractType, FuncWithContracts,
+};
+
+/// Name of the argument that restricts a relation on an `impl` block to
+/// methods with a parameter of the given type.
+const PARAM_TYPE: &str = "param_type";
+
+/// Expand a metamorphic relation attribute on a function or an `impl` block.
+pub(crate) fn relation(
+    ty: ContractType,
+    mode: ContractMode,
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let item: Item = syn::parse_quote!(#toks);
+
+    match item {
+        Item::Fn(func) => relation_fn(ty, mode, attr, func),
+        Item::Impl(impl_) => relation_impl(ty, attr, impl_),
+        _ => unimplemented!(
+            "The #[{}] attribute only works on functions and impl-blocks.",
+            ty.message_name()
+        ),
+    }
+}
+
+fn relation_fn(
+    ty: ContractType,
+    mode: ContractMode,
+    attr: TokenStream,
+    func: ItemFn,
+) -> TokenStream {
+    let f = FuncWithContracts::new_with_initial_contract(func, ty, mode, attr);
+
+    f.generate()
+}
+
+/// Generate the token-stream for an `impl` block with a relation that applies
+/// to all of its methods.
+fn relation_impl(
+    ty: ContractType,
+    attr: TokenStream,
+    mut impl_def: ItemImpl,
+) -> TokenStream {
+    match apply_to_methods(ty, attr, &mut impl_def) {
+        Ok(()) => impl_def.into_token_stream(),
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+/// Expand `#[mr_impl(deterministic, periodicity(x, +, 0))]` on an `impl`
+/// block, which applies each of the listed relations to the methods like the
+/// attribute of the relation on the `impl` block would.
+pub(crate) fn mr_impl(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let mut impl_def = match syn::parse2::<ItemImpl>(toks) {
+        Ok(impl_def) => impl_def,
+        Err(err) => {
+            let msg = "#[mr_impl] only works on impl-blocks";
+            return syn::Error::new(err.span(), msg).to_compile_error();
+        }
+    };
+
+    // the relations are applied in reverse, so that the attributes on the
+    // methods end up in the order in which they are listed
+    for relation in parse::segment_input(attr).into_iter().rev() {
+        let result = impl_relation(&relation)
+            .and_then(|(ty, args)| apply_to_methods(ty, args, &mut impl_def));
+
+        if let Err(err) = result {
+            return err.to_compile_error();
+        }
+    }
+
+    impl_def.into_token_stream()
+}
+
+/// The relation and its arguments in an entry of `#[mr_impl]`, which is
+/// written like the attribute of the relation, as in `periodicity(x, +, 0)`.
+fn impl_relation(
+    relation: &[TokenTree],
+) -> Result<(ContractType, TokenStream), syn::Error> {
+    let toks = relation.iter().cloned().collect::<TokenStream>();
+    let error = || {
+        let msg = "#[mr_impl] takes relations like `deterministic` or \
+                   `periodicity(x, +, 0)`";
+        syn::Error::new_spanned(&toks, msg)
+    };
+
+    let (name, args) = match relation {
+        [TokenTree::Ident(name)] => (name, TokenStream::new()),
+        [TokenTree::Ident(name), TokenTree::Group(args)]
+            if args.delimiter() == Delimiter::Parenthesis =>
+        {
+            (name, args.stream())
+        }
+        _ => return Err(error()),
+    };
+
+    match ContractType::contract_type_and_mode(&name.to_string()) {
+        Some((ty, _)) if ty.is_metamorphic() => Ok((ty, args)),
+        _ => {
+            let msg = format!("`{}` is not a metamorphic relation", name);
+            Err(syn::Error::new_spanned(name, msg))
+        }
+    }
+}
+
+/// Put the relation on every method of `impl_def` that it fits.
+fn apply_to_methods(
+    ty: ContractType,
+    attr: TokenStream,
+    impl_def: &mut ItemImpl,
+) -> Result<(), syn::Error> {
+    // Just like invariants, the relation is copied onto every method that it
+    // fits, the expansion of those attributes then does the actual work.
+    // The attributes of relations are named like their messages.
+    let name = ty.message_name();
+    let ident = syn::Ident::new(name, Span::call_site());
+
+    let mut segments = parse::segment_input(attr);
+
+    let param_type = take_param_type(&mut segments)?;
+
+    // the transformed variable is either a parameter, a field of one or `_`
+    // for "the parameter of type `param_type`"
+    let root = match segments.first().and_then(|seg| seg.first()) {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        Some(TokenTree::Punct(p)) if p.as_char() == '_' => String::from("_"),
+        _ => String::new(),
+    };
+
+    if root == "_" && param_type.is_none() {
+        let msg = format!(
+            "#[{}(_, ...)] needs `{} = \"Type\"` to select the parameter",
+            name, PARAM_TYPE
+        );
+        return Err(syn::Error::new(Span::call_site(), msg));
+    }
+
+    // `#[deterministic]` may leave out the variable, then it fits every
+    // method
+    let keyless = ty == ContractType::Deterministic
+        && match segments.first().map(Vec::as_slice) {
+            Some([TokenTree::Ident(_), TokenTree::Punct(p), ..]) => {
+                p.as_char() == '='
+            }
+            Some([TokenTree::Ident(_), ..])
+            | Some([TokenTree::Punct(_), ..]) => false,
+            _ => true,
+        };
+
+    for item in &mut impl_def.items {
+        if let ImplItem::Method(method) = item {
+            let mut method_segments = segments.clone();
+
+            if !keyless {
+                // methods that don't fit are skipped
+                let para = match matching_parameter(
+                    method,
+                    &root,
+                    param_type.as_ref(),
+                ) {
+                    Some(para) => para,
+                    None => continue,
+                };
+
+                if root == "_" {
+                    method_segments[0] = vec![TokenTree::Ident(para)];
+                }
+            }
+
+            let args = method_segments
+                .into_iter()
+                .map(|seg| seg.into_iter().collect::<TokenStream>());
+
+            let method_toks = quote::quote! {
+                #[#ident(#(#args),*)]
+                #method
+            };
+
+            let met: ImplItemMethod = syn::parse_quote!(#method_toks);
+
+            *method = met;
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove the `param_type = "Type"` argument and parse its type.
+///
+/// The type is given as a string since generic arguments contain commas,
+/// which would otherwise split the argument.
+fn take_param_type(
+    segments: &mut Vec<Vec<TokenTree>>,
+) -> Result<Option<Type>, syn::Error> {
+    let idx = segments
+        .iter()
+        .position(|seg| match (seg.first(), seg.get(1)) {
+            (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(p))) => {
+                ident == PARAM_TYPE && p.as_char() == '='
+            }
+            _ => false,
+        });
+
+    let idx = match idx {
+        Some(idx) => idx,
+        None => return Ok(None),
+    };
+
+    let value: TokenStream = segments.remove(idx).into_iter().skip(2).collect();
+    let lit: syn::LitStr = syn::parse2(value)?;
+
+    lit.parse().map(Some)
+}
+
+/// Find the parameter of `method` that the relation transforms, if the method
+/// has one.
+fn matching_parameter(
+    method: &ImplItemMethod,
+    root: &str,
+    param_type: Option<&Type>,
+) -> Option<syn::Ident> {
+    let type_matches = |ty: &Type| match param_type {
+        Some(expected) => {
+            ty.to_token_stream().to_string()
+                == expected.to_token_stream().to_string()
+        }
+        None => true,
+    };
+
+    for input in &method.sig.inputs {
+        let (ident, ty) = match input {
+            FnArg::Receiver(receiver) => {
+                // `self` has no written type to compare
+                if root == "self" && param_type.is_none() {
+                    return Some(syn::Ident::new(
+                        "self",
+                        receiver.self_token.span,
+                    ));
+                }
+                continue;
+            }
+            FnArg::Typed(pat_type) => match &*pat_type.pat {
+                Pat::Ident(pat) => (&pat.ident, &*pat_type.ty),
+                // the parts of a pattern have no written type to compare
+                _ => {
+                    let bound = pattern::bindings(pat_type)
+                        .into_iter()
+                        .find(|binding| binding.ident == root);
+
+                    match bound {
+                        Some(binding) if param_type.is_none() => {
+                            return Some(binding.ident)
+                        }
+                        _ => continue,
+                    }
+                }
+            },
+        };
+
+        if root == "_" && type_matches(ty) {
+            return Some(ident.clone());
+        } else if ident == root {
+            return Some(ident.clone()).filter(|_| type_matches(ty));
+        }
+    }
+
+    None
+}
@@ -2,12 +2,24 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+pub(crate) mod borrowed;
+pub(crate) mod chain;
 pub(crate) mod codegen;
+pub(crate) mod commutes_with;
+pub(crate) mod concurrent_consistency;
+pub(crate) mod deterministic;
 pub(crate) mod doc;
 pub(crate) mod ensures;
+pub(crate) mod equivalent_to;
 pub(crate) mod invariant;
+pub(crate) mod model;
 pub(crate) mod parse;
+pub(crate) mod pattern;
 pub(crate) mod requires;
+pub(crate) mod sequence;
+pub(crate) mod statistical;
+pub(crate) mod suite;
+pub(crate) mod text;
 pub(crate) mod traits;
 pub(crate) mod periodicity;
 pub(crate) mod add_not_equal;
@@ -17,14 +29,27 @@ pub(crate) mod homomorphism;
 pub(crate) mod symmetry;
 pub(crate) mod monotonicity;
 pub(crate) mod mapping;
+pub(crate) mod inclusion;
+pub(crate) mod metamorphic;
+pub(crate) mod mr_file;
+pub(crate) mod mr_seed;
 
 use quote::ToTokens;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use syn::{Expr, ItemFn};
 
+pub(crate) use commutes_with::commutes_with;
+pub(crate) use concurrent_consistency::concurrent_consistency;
+pub(crate) use deterministic::deterministic;
 pub(crate) use ensures::ensures;
+pub(crate) use equivalent_to::equivalent_to;
 pub(crate) use invariant::invariant;
-use proc_macro2::{Span, TokenStream, TokenTree};
+pub(crate) use model::model;
+use proc_macro2::{TokenStream, TokenTree};
 pub(crate) use requires::requires;
+pub(crate) use sequence::mr_sequence;
+pub(crate) use statistical::statistical;
 pub(crate) use periodicity::periodicity;
 pub(crate) use mapping::mapping;
 pub(crate) use add_not_equal::add_not_equal;
@@ -33,6 +58,10 @@ pub(crate) use iterconsistency::iter_consistency;
 pub(crate) use homomorphism::homomorphism;
 pub(crate) use symmetry::symmetry;
 pub(crate) use monotonicity::monotonicity;
+pub(crate) use inclusion::inclusion;
+pub(crate) use mr_file::mr_file;
+pub(crate) use metamorphic::mr_impl;
+pub(crate) use suite::{define_mr_suite, mr_suite};
 pub(crate) use traits::{contract_trait_item_impl, contract_trait_item_trait};
 
 /// Checking-mode of a contract.
@@ -102,6 +131,13 @@ pub(crate) enum ContractType {
     Homomorphism,
     IterConsistency,
     Mapping,
+    Deterministic,
+    Inclusion,
+    EquivalentTo,
+    Model,
+    CommutesWith,
+    ConcurrentConsistency,
+    Statistical,
 }
 
 impl ContractType {
@@ -120,9 +156,48 @@ impl ContractType {
             ContractType::Homomorphism => "homomorphism",
             ContractType::IterConsistency => "iter_consistency",
             ContractType::Mapping => "mapping",
+            ContractType::Deterministic => "deterministic",
+            ContractType::Inclusion => "inclusion",
+            ContractType::EquivalentTo => "equivalent_to",
+            ContractType::Model => "model",
+            ContractType::CommutesWith => "commutes_with",
+            ContractType::ConcurrentConsistency => "concurrent_consistency",
+            ContractType::Statistical => "statistical",
         }
     }
 
+    /// Whether the contract is a metamorphic relation, which is checked by
+    /// executing the function body again on transformed inputs.
+    pub(crate) fn is_metamorphic(self) -> bool {
+        matches!(
+            self,
+            ContractType::Periodicity
+                | ContractType::AddNotEqual
+                | ContractType::DimensionTrans
+                | ContractType::Monotonicity
+                | ContractType::Symmetry
+                | ContractType::Homomorphism
+                | ContractType::IterConsistency
+                | ContractType::Mapping
+                | ContractType::Deterministic
+                | ContractType::Inclusion
+        )
+    }
+
+    /// Whether the contract takes named `name = expr` arguments besides its
+    /// positional ones.
+    pub(crate) fn takes_options(self) -> bool {
+        self.is_metamorphic()
+            || matches!(
+                self,
+                ContractType::EquivalentTo
+                    | ContractType::Model
+                    | ContractType::CommutesWith
+                    | ContractType::ConcurrentConsistency
+                    | ContractType::Statistical
+            )
+    }
+
     /// Determine the type and mode of an identifier.
     pub(crate) fn contract_type_and_mode(
         ident: &str,
@@ -157,20 +232,41 @@ impl ContractType {
             "homomorphism" => Some((ContractType::Homomorphism, ContractMode::Test)),
             "iter_consistency" => Some((ContractType::IterConsistency, ContractMode::Test)),
             "mapping" => Some((ContractType::Mapping, ContractMode::Test)),
+            "deterministic" => {
+                Some((ContractType::Deterministic, ContractMode::Test))
+            }
+            "inclusion" => Some((ContractType::Inclusion, ContractMode::Test)),
+            "equivalent_to" => {
+                Some((ContractType::EquivalentTo, ContractMode::Test))
+            }
+            "model" => Some((ContractType::Model, ContractMode::Test)),
+            "commutes_with" => {
+                Some((ContractType::CommutesWith, ContractMode::Test))
+            }
+            "concurrent_consistency" => {
+                Some((ContractType::ConcurrentConsistency, ContractMode::Test))
+            }
+            "statistical" => {
+                Some((ContractType::Statistical, ContractMode::Test))
+            }
             _ => None,
         }
     }
 }
 
 /// Representation of a contract
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct Contract {
-    pub(crate) span: Span,
     pub(crate) ty: ContractType,
     pub(crate) mode: ContractMode,
     pub(crate) assertions: Vec<Expr>,
     pub(crate) streams: Vec<TokenStream>,
     pub(crate) desc: Option<String>,
+    /// Named `name = expr` arguments of metamorphic relations.
+    pub(crate) options: HashMap<String, Expr>,
+    /// The step of a chain this relation checks, if the chain is compared
+    /// step by step, see [`chain::split`].
+    pub(crate) step: Option<usize>,
 }
 
 impl Contract {
@@ -179,19 +275,29 @@ impl Contract {
         mode: ContractMode,
         toks: TokenStream,
     ) -> Self {
-        let (assertions, streams, desc) = parse::parse_attributes(toks);
+        let (mut assertions, mut streams, desc) = parse::parse_attributes(toks);
 
-        let span = Span::call_site();
+        let options = if ty.takes_options() {
+            parse::extract_options(&mut assertions, &mut streams)
+        } else {
+            HashMap::new()
+        };
 
         Self {
-            span,
             ty,
             mode,
             assertions,
             streams,
             desc,
+            options,
+            step: None,
         }
     }
+
+    /// Get the value of a named `name = expr` argument.
+    pub(crate) fn option(&self, name: &str) -> Option<&Expr> {
+        self.options.get(name)
+    }
 }
 
 /// A function that is annotated with contracts
@@ -199,6 +305,11 @@ impl Contract {
 pub(crate) struct FuncWithContracts {
     pub(crate) contracts: Vec<Contract>,
     pub(crate) function: ItemFn,
+    /// The files of `#[mr_file]` attributes that relations were read from.
+    pub(crate) files: Vec<PathBuf>,
+    /// The problems with those files, which are reported instead of the
+    /// function.
+    pub(crate) errors: Vec<syn::Error>,
 }
 
 impl FuncWithContracts {
@@ -208,17 +319,26 @@ impl FuncWithContracts {
     /// The initial contract is parsed from the tokens, others will be read from
     /// parsed function.
     pub(crate) fn new_with_initial_contract(
-        mut func: ItemFn,
+        func: ItemFn,
         cty: ContractType,
         cmode: ContractMode,
         ctoks: TokenStream,
     ) -> Self {
         // add in the first attribute
-        let mut contracts: Vec<Contract> = {
-            let initial_contract = Contract::from_toks(cty, cmode, ctoks);
-            vec![initial_contract]
-        };
+        let initial_contract = Contract::from_toks(cty, cmode, ctoks);
+
+        Self::new_with_contracts(func, vec![initial_contract])
+    }
 
+    /// Create a `FuncWithContracts` value from already parsed contracts and a
+    /// parsed version of the function.
+    ///
+    /// Other contracts will be read from the parsed function, including the
+    /// relations of `#[mr_file]` and `#[mr_suite]` attributes.
+    pub(crate) fn new_with_contracts(
+        mut func: ItemFn,
+        mut contracts: Vec<Contract>,
+    ) -> Self {
         // find all other attributes
 
         let contract_attrs = func
@@ -235,12 +355,14 @@ impl FuncWithContracts {
                 //
                 // this is a hack to get to the inner token stream.
 
-                let tok_tree = a.tokens.clone().into_iter().next().unwrap();
+                let tok_tree = a.tokens.clone().into_iter().next();
                 let toks = match tok_tree {
-                    TokenTree::Group(group) => group.stream(),
-                    TokenTree::Ident(i) => i.into_token_stream(),
-                    TokenTree::Punct(p) => p.into_token_stream(),
-                    TokenTree::Literal(l) => l.into_token_stream(),
+                    Some(TokenTree::Group(group)) => group.stream(),
+                    Some(TokenTree::Ident(i)) => i.into_token_stream(),
+                    Some(TokenTree::Punct(p)) => p.into_token_stream(),
+                    Some(TokenTree::Literal(l)) => l.into_token_stream(),
+                    // attributes without arguments, like `#[deterministic]`
+                    None => TokenStream::new(),
                 };
 
                 Contract::from_toks(ty, mode, toks)
@@ -248,9 +370,55 @@ impl FuncWithContracts {
 
         contracts.extend(contract_attrs);
 
+        // relations that are declared in files
+        let mut files = vec![];
+        let mut errors = vec![];
+
+        for attr in func.attrs.iter().filter(|a| mr_file::is_mr_file(a)) {
+            let loaded = mr_file::path(attr)
+                .and_then(|path| mr_file::load_relations(&func, &path));
+
+            match loaded {
+                Ok((file, relations)) => {
+                    files.push(file);
+                    contracts.extend(relations);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+
+        // relations of suites
+        let suites = func.attrs.iter().filter(|a| suite::is_mr_suite(a));
+        for attr in suites {
+            contracts.extend(suite::attribute_contracts(attr));
+        }
+
+        // a relation that only runs the function again may leave out the
+        // variable it clones
+        for contract in &mut contracts {
+            deterministic::default_input(contract, &func);
+        }
+
+        // a chain of transformations takes the place of the operator and the
+        // modifier
+        for contract in &mut contracts {
+            chain::make_room(contract);
+        }
+
+        // a chain that is compared step by step is checked by one relation
+        // per step
+        let mut split = vec![];
+        for contract in contracts {
+            match chain::split(contract) {
+                Ok(steps) => split.extend(steps),
+                Err(err) => errors.push(err),
+            }
+        }
+        let contracts = split;
+
         // remove contract attributes
         {
-            let attrs = std::mem::replace(&mut func.attrs, vec![]);
+            let attrs = std::mem::take(&mut func.attrs);
 
             let other_attrs = attrs
                 .into_iter()
@@ -259,6 +427,8 @@ impl FuncWithContracts {
                         &attr.path.segments.last().unwrap().ident.to_string(),
                     )
                     .is_none()
+                        && !mr_file::is_mr_file(attr)
+                        && !suite::is_mr_suite(attr)
                 })
                 .collect();
 
@@ -268,12 +438,23 @@ impl FuncWithContracts {
         Self {
             function: func,
             contracts,
+            files,
+            errors,
         }
     }
 
     /// Generates the resulting tokens including all contract-checks
     pub(crate) fn generate(mut self) -> TokenStream {
-        let doc_attrs = doc::generate_attributes(&self.contracts);
+        if !self.errors.is_empty() {
+            return self
+                .errors
+                .iter()
+                .map(syn::Error::to_compile_error)
+                .collect();
+        }
+
+        let doc_attrs =
+            doc::generate_attributes(&self.contracts, &self.function);
         let olds = codegen::extract_old_calls(&mut self.contracts);
 
         codegen::generate(self, doc_attrs, olds)
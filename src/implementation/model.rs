@@ -0,0 +1,301 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{FnArg, Ident, ImplItem, Item, ItemFn, ItemImpl, Type};
+
+use crate::implementation::{
+    equivalent_to::{self, Compared},
+    parse, Contract, ContractMode, ContractType, FuncWithContracts,
+};
+
+/// Name of the argument that excludes methods of an `impl` block from the
+/// model.
+const SKIP: &str = "skip";
+
+pub(crate) fn model(
+    mode: ContractMode,
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let item: Item = syn::parse_quote!(#toks);
+
+    match item {
+        Item::Fn(func) => model_fn(mode, attr, func),
+        Item::Impl(impl_) => model_impl(attr, impl_),
+        _ => unimplemented!(
+            "The #[model] attribute only works on functions and impl-blocks."
+        ),
+    }
+}
+
+fn model_fn(
+    mode: ContractMode,
+    attr: TokenStream,
+    func: ItemFn,
+) -> TokenStream {
+    let ty = ContractType::Model;
+
+    let f = FuncWithContracts::new_with_initial_contract(func, ty, mode, attr);
+
+    f.generate()
+}
+
+/// Generate the token-stream for an `impl` block whose methods are mirrored on
+/// a model.
+fn model_impl(attr: TokenStream, mut impl_def: ItemImpl) -> TokenStream {
+    // Just like invariants, the attribute is copied onto every method that
+    // takes `self`, the expansion of those attributes then does the actual
+    // work.
+    let mut segments = parse::segment_input(attr);
+
+    let skipped = match take_skipped(&mut segments) {
+        Ok(skipped) => skipped,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let args = segments
+        .into_iter()
+        .map(|seg| seg.into_iter().collect::<TokenStream>());
+    let attr = quote::quote!(#(#args),*);
+
+    for item in &mut impl_def.items {
+        if let ImplItem::Method(method) = item {
+            let uses_self =
+                matches!(method.sig.inputs.first(), Some(FnArg::Receiver(_)));
+
+            if !uses_self || skipped.contains(&method.sig.ident) {
+                continue;
+            }
+
+            method.attrs.insert(0, syn::parse_quote!(#[model(#attr)]));
+        }
+    }
+
+    impl_def.into_token_stream()
+}
+
+/// Remove the `skip = [method, ...]` argument and return the listed methods.
+fn take_skipped(
+    segments: &mut Vec<Vec<TokenTree>>,
+) -> Result<Vec<Ident>, syn::Error> {
+    let idx = segments
+        .iter()
+        .position(|seg| match (seg.first(), seg.get(1)) {
+            (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(p))) => {
+                ident == SKIP && p.as_char() == '='
+            }
+            _ => false,
+        });
+
+    let idx = match idx {
+        Some(idx) => idx,
+        None => return Ok(vec![]),
+    };
+
+    let value: TokenStream = segments.remove(idx).into_iter().skip(2).collect();
+    let methods: syn::ExprArray = syn::parse2(value)?;
+
+    methods
+        .elems
+        .iter()
+        .map(|method| match method {
+            syn::Expr::Path(path) if path.path.get_ident().is_some() => {
+                Ok(path.path.get_ident().unwrap().clone())
+            }
+            method => Err(syn::Error::new_spanned(
+                method,
+                "`skip` takes a list of method names",
+            )),
+        })
+        .collect()
+}
+
+/// Generate the call of the method on the model, which is placed before the
+/// method body, and the comparisons with the real results, which are placed
+/// after it.
+///
+/// The model starts out as the abstraction of `self` and is given the same
+/// arguments as the method. Afterwards the return values, the mutated
+/// parameters and the abstraction of `self` have to match the model.
+pub(crate) fn mirror(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+) -> (TokenStream, TokenStream) {
+    match try_mirror(contract, index, func) {
+        Ok(result) => result,
+        Err(err) => (err.to_compile_error(), TokenStream::new()),
+    }
+}
+
+fn try_mirror(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+) -> Result<(TokenStream, TokenStream), syn::Error> {
+    let mode = contract.mode.final_mode();
+
+    let cfg = match equivalent_to::mode_cfg(mode) {
+        Some(cfg) => cfg,
+        None => return Ok((TokenStream::new(), TokenStream::new())),
+    };
+
+    let model_ty: Type = match contract.streams.first() {
+        Some(ty) => syn::parse2(ty.clone())?,
+        None => {
+            let msg = "#[model] needs the type of the model";
+            return Err(syn::Error::new(Span::call_site(), msg));
+        }
+    };
+
+    let abs = match contract.option("abs") {
+        Some(abs) => abs,
+        None => {
+            let msg = "#[model] needs `abs = |value| ...` to abstract `self` \
+                       into the model";
+            return Err(syn::Error::new(Span::call_site(), msg));
+        }
+    };
+
+    let receiver = match func.sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) => receiver,
+        _ => {
+            let msg = "#[model] only works on methods taking `self`";
+            return Err(syn::Error::new_spanned(&func.sig, msg));
+        }
+    };
+
+    let method = &func.sig.ident;
+    let func_name = method.to_string();
+    let model_name = model_ty.to_token_stream().to_string().replace(' ', "");
+
+    let state_ident = |name: &str| {
+        let name = format!("__contract_model_{}_{}", index, name);
+        Ident::new(&name, Span::call_site())
+    };
+
+    let model = state_ident("self");
+    let self_ref = if receiver.reference.is_some() {
+        quote::quote!(&*self)
+    } else {
+        quote::quote!(&self)
+    };
+
+    let mut inputs = TokenStream::new();
+    let mut args = vec![];
+    let mut compared = vec![];
+
+    for input in func.sig.inputs.iter().skip(1) {
+        if let FnArg::Typed(pat_type) = input {
+            args.push(equivalent_to::typed_argument(
+                contract.ty,
+                pat_type,
+                &state_ident,
+                &cfg,
+                &mut inputs,
+                &mut compared,
+            )?);
+        }
+    }
+
+    // the abstraction is passed through a function so that the argument type
+    // of a closure is inferred from the bound
+    let abstraction_fn = state_ident("abstraction_fn");
+    let abstraction = quote::quote! {
+        fn #abstraction_fn<T: ?Sized, M>(value: &T, abs: impl Fn(&T) -> M) -> M {
+            abs(value)
+        }
+    };
+
+    let model_ret = state_ident("ret");
+    let call = quote::quote! {
+        #cfg
+        #[allow(unused_mut)]
+        let mut #model: #model_ty = #abstraction_fn(#self_ref, #abs);
+
+        #inputs
+
+        #cfg
+        let #model_ret = #model.#method(#(#args),*);
+    };
+
+    let desc =
+        equivalent_to::escape(&equivalent_to::violation(contract, &func_name));
+    let model_name = equivalent_to::escape(&model_name);
+
+    let mut checks = TokenStream::new();
+
+    // `self` can only be compared if the method did not consume it
+    if receiver.reference.is_some() {
+        let abstracted = state_ident("abstraction");
+        checks.extend(quote::quote! {
+            let #abstracted: #model_ty = #abstraction_fn(&*self, #abs);
+        });
+
+        compared.insert(
+            0,
+            Compared {
+                name: String::from("self"),
+                actual: abstracted.into_token_stream(),
+                expected: model.into_token_stream(),
+            },
+        );
+    }
+
+    compared.insert(
+        0,
+        Compared {
+            name: String::from("ret"),
+            actual: quote::quote!(ret),
+            expected: model_ret.into_token_stream(),
+        },
+    );
+
+    for Compared {
+        name,
+        actual,
+        expected,
+    } in compared
+    {
+        let message = match name.as_str() {
+            "ret" => format!(
+                "{}: `{}::{}` returned {{:?}}, but `{}` returned {{:?}}",
+                desc, model_name, func_name, func_name
+            ),
+            "self" => format!(
+                "{}: the model is {{:?}} after `{}`, but `self` is \
+                 abstracted to {{:?}}",
+                desc, func_name
+            ),
+            _ => format!(
+                "{}: `{}` is {{:?}} after `{}::{}`, but {{:?}} after `{}`",
+                desc, name, model_name, func_name, func_name
+            ),
+        };
+
+        let equal = equivalent_to::equality(contract, &actual, &expected);
+
+        checks.extend(equivalent_to::assertion(
+            mode, &equal, &message, &expected, &actual,
+        ));
+    }
+
+    let call = quote::quote! {
+        #cfg
+        #abstraction
+
+        #call
+    };
+
+    let checks = quote::quote! {
+        #cfg
+        {
+            #checks
+        }
+    };
+
+    Ok((call, checks))
+}
@@ -0,0 +1,313 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::{Attribute, ImplItem, Item, ItemFn, ItemImpl, LitStr};
+use toml::Value;
+
+use crate::implementation::{Contract, ContractType, FuncWithContracts};
+
+/// Keys of positional arguments, in the order they are passed to the
+/// relation attribute.
+const POSITIONAL: &[&str] =
+    &["input", "op", "modifier", "result_op", "result_modifier"];
+
+/// Keys of the named `name = expr` arguments.
+const NAMED: &[&str] = &["output", "on", "when", "tolerance", "transform"];
+
+/// Keys of the positional arguments that `transform` replaces.
+const REPLACED: &[&str] = &["op", "modifier"];
+
+pub(crate) fn mr_file(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let item: Item = syn::parse_quote!(#toks);
+
+    let path = match syn::parse2::<LitStr>(attr) {
+        Ok(path) => path,
+        Err(err) => {
+            let msg = "#[mr_file] takes the path of the relation file as a \
+                       string";
+            return syn::Error::new(err.span(), msg).to_compile_error();
+        }
+    };
+
+    match item {
+        Item::Fn(mut func) => {
+            // the relations are loaded like those of a `#[mr_file]` that
+            // follows another contract attribute
+            func.attrs.insert(0, syn::parse_quote!(#[mr_file(#path)]));
+
+            FuncWithContracts::new_with_contracts(func, vec![]).generate()
+        }
+        Item::Impl(impl_) => mr_file_impl(&path, impl_),
+        _ => unimplemented!(
+            "The #[mr_file] attribute only works on functions and impl-blocks."
+        ),
+    }
+}
+
+/// Generate the token-stream for an `impl` block whose methods have
+/// relations in a file.
+fn mr_file_impl(path: &LitStr, mut impl_def: ItemImpl) -> TokenStream {
+    // Just like invariants, the attribute is copied onto the methods, which
+    // then load their own relations.
+    let relations = match read_relations(path) {
+        Ok((_, relations)) => relations,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    for item in &mut impl_def.items {
+        if let ImplItem::Method(method) = item {
+            if !relations.contains_key(&method.sig.ident.to_string()) {
+                continue;
+            }
+
+            method.attrs.insert(0, syn::parse_quote!(#[mr_file(#path)]));
+        }
+    }
+
+    impl_def.into_token_stream()
+}
+
+/// Whether `attr` is a `#[mr_file]` attribute.
+pub(crate) fn is_mr_file(attr: &Attribute) -> bool {
+    attr.path.segments.last().unwrap().ident == "mr_file"
+}
+
+/// The path given to a `#[mr_file]` attribute.
+pub(crate) fn path(attr: &Attribute) -> Result<LitStr, syn::Error> {
+    attr.parse_args::<LitStr>().map_err(|err| {
+        let msg = "#[mr_file] takes the path of the relation file as a string";
+        syn::Error::new(err.span(), msg)
+    })
+}
+
+/// Read the relations of `func` from the file at `path`, which is relative
+/// to the manifest of the crate being compiled, and return the file as well.
+///
+/// Problems with the file are reported at `path`.
+pub(crate) fn load_relations(
+    func: &ItemFn,
+    path: &LitStr,
+) -> Result<(PathBuf, Vec<Contract>), syn::Error> {
+    let (file, relations) = read_relations(path)?;
+
+    let name = func.sig.ident.to_string();
+
+    let entries = match relations.get(&name) {
+        Some(Value::Array(entries)) => entries.as_slice(),
+        Some(_) => {
+            let msg = format!(
+                "the relations of `{}` in {} must be an array of tables",
+                name,
+                path.value()
+            );
+            return Err(syn::Error::new_spanned(path, msg));
+        }
+        None => &[],
+    };
+
+    let contracts = entries
+        .iter()
+        .map(|entry| relation_contract(entry, &name, path))
+        .collect::<Result<_, _>>()?;
+
+    Ok((file, contracts))
+}
+
+/// Include the relation files into the function, so that changing one of
+/// them causes a rebuild.
+pub(crate) fn include(files: &[PathBuf]) -> TokenStream {
+    files
+        .iter()
+        .map(|file| {
+            let file = LitStr::new(&file.to_string_lossy(), Span::call_site());
+
+            quote::quote! {
+                const _: &[u8] = include_bytes!(#file);
+            }
+        })
+        .collect()
+}
+
+fn read_relations(
+    path: &LitStr,
+) -> Result<(PathBuf, toml::value::Table), syn::Error> {
+    let error = |msg: String| syn::Error::new_spanned(path, msg);
+
+    let dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|_| {
+        error(String::from(
+            "#[mr_file] needs CARGO_MANIFEST_DIR to find the file",
+        ))
+    })?;
+    let file = Path::new(&dir).join(path.value());
+
+    let text = std::fs::read_to_string(&file).map_err(|err| {
+        error(format!(
+            "can't read relation file {}: {}",
+            file.display(),
+            err
+        ))
+    })?;
+
+    let relations = match text.parse::<Value>() {
+        Ok(Value::Table(relations)) => relations,
+        Ok(_) => {
+            let msg =
+                format!("relation file {} must contain tables", path.value());
+            return Err(error(msg));
+        }
+        Err(err) => {
+            let msg =
+                format!("invalid relation file {}: {}", path.value(), err);
+            return Err(error(msg));
+        }
+    };
+
+    Ok((file, relations))
+}
+
+/// Translate an entry of a relation file into the contract an inline
+/// attribute with the same arguments would produce.
+fn relation_contract(
+    entry: &Value,
+    name: &str,
+    path: &LitStr,
+) -> Result<Contract, syn::Error> {
+    let error = |msg: String| {
+        let msg = format!("{} in {}", msg, path.value());
+        syn::Error::new_spanned(path, msg)
+    };
+
+    let entry = entry.as_table().ok_or_else(|| {
+        error(format!("the relations of `{}` must be tables", name))
+    })?;
+
+    for key in entry.keys() {
+        let known = key == "relation"
+            || key == "description"
+            || POSITIONAL.contains(&key.as_str())
+            || NAMED.contains(&key.as_str());
+
+        if !known {
+            let msg =
+                format!("unknown key `{}` in a relation of `{}`", key, name);
+            return Err(error(msg));
+        }
+    }
+
+    let relation = match entry.get("relation") {
+        Some(Value::String(relation)) => relation.as_str(),
+        _ => {
+            let msg = format!("a relation of `{}` has no `relation`", name);
+            return Err(error(msg));
+        }
+    };
+
+    let (ty, mode) = ContractType::contract_type_and_mode(relation)
+        .filter(|(ty, _)| ty.is_metamorphic())
+        .ok_or_else(|| {
+            error(format!("`{}` is not a metamorphic relation", relation))
+        })?;
+
+    let mut args = vec![];
+
+    // a chain of transformations replaces the operator and the modifier
+    let positional = POSITIONAL
+        .iter()
+        .filter(|key| {
+            !(entry.contains_key("transform") && REPLACED.contains(key))
+        })
+        .copied()
+        .collect::<Vec<_>>();
+
+    // positional arguments can't be left out in between
+    let given = positional
+        .iter()
+        .take_while(|key| entry.contains_key(**key))
+        .count();
+
+    if given == 0 {
+        let msg = format!("a relation of `{}` has no `input`", name);
+        return Err(error(msg));
+    }
+
+    if let Some(key) = positional[given..]
+        .iter()
+        .find(|key| entry.contains_key(**key))
+    {
+        let msg = format!(
+            "`{}` of a relation of `{}` needs `{}`",
+            key, name, positional[given]
+        );
+        return Err(error(msg));
+    }
+
+    for key in &positional[..given] {
+        args.push(tokens(&entry[*key], key).map_err(error)?);
+    }
+
+    for key in NAMED {
+        if let Some(value) = entry.get(*key) {
+            let ident = syn::Ident::new(key, Span::call_site());
+            let value = tokens(value, key).map_err(error)?;
+
+            args.push(quote::quote!(#ident = #value));
+        }
+    }
+
+    if let Some(desc) = entry.get("description") {
+        let desc = desc.as_str().ok_or_else(|| {
+            error(String::from("the description must be a string"))
+        })?;
+
+        args.push(LitStr::new(desc, Span::call_site()).into_token_stream());
+    }
+
+    Ok(Contract::from_toks(ty, mode, quote::quote!(#(#args),*)))
+}
+
+/// Strings hold Rust code, numbers and booleans are used as literals.
+fn tokens(value: &Value, key: &str) -> Result<TokenStream, String> {
+    let code = match value {
+        Value::String(code) => code.clone(),
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => {
+            value.to_string()
+        }
+        _ => {
+            return Err(format!(
+                "`{}` of a relation must be a string or a number",
+                key
+            ))
+        }
+    };
+
+    code.parse()
+        .map_err(|err| format!("invalid `{}` \"{}\": {}", key, code, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_keys_are_reported_at_the_path() {
+        let path: LitStr = syn::parse_quote!("relations.toml");
+        let entry = "relation = \"periodicity\"\ninput = \"x\"\nshift = 1"
+            .parse::<Value>()
+            .unwrap();
+
+        let err = match relation_contract(&entry, "wrap", &path) {
+            Ok(_) => panic!("expected an error for the unknown key"),
+            Err(err) => err,
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "unknown key `shift` in a relation of `wrap` in relations.toml"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Span, TokenStream};
+use syn::{
+    FnArg, GenericParam, Generics, Ident, ItemFn, Pat, PatType, Type,
+    TypeParamBound, WherePredicate,
+};
+
+/// Name of the attribute that marks a parameter as a random number generator.
+const MR_SEED: &str = "mr_seed";
+
+/// Whether `pat_type` is a random number generator, because it is marked with
+/// `#[mr_seed]` or its type is named like one.
+///
+/// Whether the generator is cloned or rebuilt from a seed for the follow-up
+/// runs is left to the type checker, see [`helpers`].
+pub(crate) fn is_seeded(pat_type: &PatType, generics: &Generics) -> bool {
+    pat_type
+        .attrs
+        .iter()
+        .any(|attr| attr.path.is_ident(MR_SEED))
+        || match &*pat_type.ty {
+            Type::Reference(ty) if ty.mutability.is_some() => {
+                is_rng(&ty.elem, generics)
+            }
+            _ => false,
+        }
+}
+
+/// Whether `pat_type` is a `&mut` parameter that is named like a random number
+/// generator or has a type named like one, even though it is not recognized
+/// as one by [`is_seeded`].
+pub(crate) fn looks_like_rng(pat_type: &PatType) -> bool {
+    let ty = match &*pat_type.ty {
+        Type::Reference(ty) if ty.mutability.is_some() => &ty.elem,
+        _ => return false,
+    };
+
+    let named = match &*pat_type.pat {
+        Pat::Ident(pat) => {
+            let name = pat.ident.to_string().to_lowercase();
+            name.contains("rng") || name.contains("random")
+        }
+        _ => false,
+    };
+    let typed = match &**ty {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(segment) => {
+                let name = segment.ident.to_string();
+                name.contains("Rng") || name.contains("Random")
+            }
+            None => false,
+        },
+        _ => false,
+    };
+
+    named || typed
+}
+
+/// Remove the `#[mr_seed]` attributes from the parameters of `func` and
+/// return the names of all parameters that are random number generators.
+pub(crate) fn take_seeded(func: &mut ItemFn) -> Result<Vec<Ident>, syn::Error> {
+    let generics = func.sig.generics.clone();
+    let mut seeded = vec![];
+
+    for input in &mut func.sig.inputs {
+        let pat_type = match input {
+            FnArg::Typed(pat_type) => pat_type,
+            FnArg::Receiver(_) => continue,
+        };
+
+        if !is_seeded(pat_type, &generics) {
+            continue;
+        }
+
+        let by_mut_ref = matches!(
+            &*pat_type.ty,
+            Type::Reference(ty) if ty.mutability.is_some()
+        );
+
+        match &*pat_type.pat {
+            Pat::Ident(pat) if by_mut_ref => seeded.push(pat.ident.clone()),
+            _ => {
+                let msg = "#[mr_seed] only works on named `&mut` parameters";
+                return Err(syn::Error::new_spanned(&*pat_type, msg));
+            }
+        }
+
+        pat_type.attrs.retain(|attr| !attr.path.is_ident(MR_SEED));
+    }
+
+    Ok(seeded)
+}
+
+/// Seed of the generators that are rebuilt for the follow-up runs.
+const SEED: u64 = 0x5eed;
+
+/// Whether one of the generators in `seeded` has a type named like one of
+/// `rand`, so [`helpers`] may rebuild it with `rand` if it is not `Clone`.
+///
+/// A generator that is only marked with `#[mr_seed]` has to be `Clone`, which
+/// keeps hand-written generators free of `rand`.
+pub(crate) fn uses_rand(func: &ItemFn, seeded: &[Ident]) -> bool {
+    func.sig.inputs.iter().any(|input| match input {
+        FnArg::Typed(pat_type) => match (&*pat_type.pat, &*pat_type.ty) {
+            (Pat::Ident(pat), Type::Reference(ty)) => {
+                seeded.contains(&pat.ident)
+                    && is_rng(&ty.elem, &func.sig.generics)
+            }
+            _ => false,
+        },
+        FnArg::Receiver(_) => false,
+    })
+}
+
+/// The helpers of [`fresh`], which are defined once in the function body.
+///
+/// A generator that is `Clone` is cloned before the original call, so the
+/// follow-up runs start from the state the original call started from. For
+/// a generator that keeps its state elsewhere, like `ThreadRng` which shares
+/// the state of its thread with all its clones, they still draw different
+/// numbers.
+///
+/// With `rand`, a generator that is only `SeedableRng` is rebuilt from a
+/// fixed seed, and any other, like `impl Rng`, is replaced by a `StdRng`
+/// from that seed.
+pub(crate) fn helpers(rand: bool) -> TokenStream {
+    let clone = quote::quote! {
+        #[allow(dead_code)]
+        struct __ContractGenerator<'a, R>(&'a R);
+
+        trait __ContractClone<R> {
+            fn fresh(&self) -> R;
+        }
+
+        impl<R: ::std::clone::Clone> __ContractClone<R>
+            for &&__ContractGenerator<'_, R>
+        {
+            fn fresh(&self) -> R {
+                ::std::clone::Clone::clone(self.0)
+            }
+        }
+    };
+
+    if !rand {
+        return clone;
+    }
+
+    quote::quote! {
+        #clone
+
+        trait __ContractReseed<R> {
+            fn fresh(&self) -> R;
+        }
+
+        impl<R: ::rand::SeedableRng> __ContractReseed<R>
+            for &__ContractGenerator<'_, R>
+        {
+            fn fresh(&self) -> R {
+                R::seed_from_u64(#SEED)
+            }
+        }
+
+        trait __ContractStandIn {
+            fn fresh(&self) -> ::rand::rngs::StdRng;
+        }
+
+        impl<R> __ContractStandIn for __ContractGenerator<'_, R> {
+            fn fresh(&self) -> ::rand::rngs::StdRng {
+                ::rand::SeedableRng::seed_from_u64(#SEED)
+            }
+        }
+    }
+}
+
+/// A new generator made from the generator behind the reference `source`,
+/// which is left as it is.
+pub(crate) fn fresh(source: TokenStream) -> TokenStream {
+    quote::quote!((&&&__ContractGenerator(#source)).fresh())
+}
+
+/// Take a copy of each generator before the original call, which keeps
+/// using the generator of the caller.
+pub(crate) fn snapshot(func: &ItemFn, seeded: &[Ident]) -> TokenStream {
+    if seeded.is_empty() {
+        return TokenStream::new();
+    }
+
+    let helpers = helpers(uses_rand(func, seeded));
+    let snapshots = seeded.iter().map(|ident| {
+        let seed = seed_ident(ident);
+        let fresh = fresh(quote::quote!(&*#ident));
+
+        quote::quote! {
+            let #seed = #fresh;
+        }
+    });
+
+    quote::quote! {
+        #helpers
+        #(#snapshots)*
+    }
+}
+
+/// Give each follow-up run a new generator from the copy taken before the
+/// original call, so that all of them start from the same state.
+pub(crate) fn reseed(seeded: &[Ident]) -> TokenStream {
+    seeded
+        .iter()
+        .map(|ident| {
+            let seed = seed_ident(ident);
+            let state = Ident::new(
+                &format!("__contract_rng_{}", ident),
+                Span::call_site(),
+            );
+            let fresh = fresh(quote::quote!(&#seed));
+
+            quote::quote! {
+                let mut #state = #fresh;
+                let #ident = &mut #state;
+            }
+        })
+        .collect()
+}
+
+fn seed_ident(ident: &Ident) -> Ident {
+    Ident::new(&format!("__contract_seed_{}", ident), Span::call_site())
+}
+
+/// Whether `ty` is a generator type like `StdRng`, `impl RngCore` or a
+/// generic parameter bound by such a trait.
+fn is_rng(ty: &Type, generics: &Generics) -> bool {
+    match ty {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(segment) if names_rng(&segment.ident) => true,
+            Some(segment) => {
+                path.path.segments.len() == 1
+                    && bound_by_rng(&segment.ident, generics)
+            }
+            None => false,
+        },
+        Type::ImplTrait(ty) => ty.bounds.iter().any(is_rng_bound),
+        Type::Paren(ty) => is_rng(&ty.elem, generics),
+        Type::Group(ty) => is_rng(&ty.elem, generics),
+        _ => false,
+    }
+}
+
+/// Whether the generic parameter `ident` is bound by a generator trait.
+fn bound_by_rng(ident: &Ident, generics: &Generics) -> bool {
+    let in_params = generics.params.iter().any(|param| match param {
+        GenericParam::Type(param) => {
+            param.ident == *ident && param.bounds.iter().any(is_rng_bound)
+        }
+        _ => false,
+    });
+
+    let in_where = generics.where_clause.iter().any(|clause| {
+        clause.predicates.iter().any(|predicate| match predicate {
+            WherePredicate::Type(predicate) => {
+                matches!(
+                    &predicate.bounded_ty,
+                    Type::Path(path) if path.path.is_ident(ident)
+                ) && predicate.bounds.iter().any(is_rng_bound)
+            }
+            _ => false,
+        })
+    });
+
+    in_params || in_where
+}
+
+fn is_rng_bound(bound: &TypeParamBound) -> bool {
+    match bound {
+        TypeParamBound::Trait(bound) => matches!(
+            bound.path.segments.last(),
+            Some(segment) if names_rng(&segment.ident)
+        ),
+        TypeParamBound::Lifetime(_) => false,
+    }
+}
+
+fn names_rng(ident: &Ident) -> bool {
+    let name = ident.to_string();
+
+    name.ends_with("Rng") || name == "RngCore"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot_of(mut func: ItemFn) -> String {
+        let seeded = take_seeded(&mut func).unwrap();
+
+        snapshot(&func, &seeded).to_string()
+    }
+
+    #[test]
+    fn only_generators_of_rand_use_rand() {
+        let hand_written = snapshot_of(syn::parse_quote! {
+            fn noisy(#[mr_seed] rng: &mut Lcg, x: f64) -> f64 {
+                x + rng.next_f64()
+            }
+        });
+        assert!(hand_written.contains("__ContractClone"));
+        assert!(!hand_written.contains("rand"));
+
+        let std_rng = snapshot_of(syn::parse_quote! {
+            fn noisy(rng: &mut StdRng, x: f64) -> f64 {
+                x + rng.gen::<f64>()
+            }
+        });
+        assert!(std_rng.contains(":: rand :: SeedableRng"));
+        assert!(std_rng.contains(":: rand :: rngs :: StdRng"));
+    }
+}
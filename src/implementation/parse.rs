@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use proc_macro2::{Spacing, TokenStream, TokenTree};
+use std::collections::HashMap;
 use syn::{Expr, ExprLit, Lit};
 
 /// Parse attributes into a list of expression and an optional description of
@@ -45,6 +46,41 @@ pub(crate) fn parse_attributes(
     (conds, segments_stream, desc)
 }
 
+/// Remove all `name = expr` arguments from the parsed attribute and return
+/// them by name.
+///
+/// Metamorphic relations take positional arguments, so named arguments can be
+/// placed anywhere without shifting the positions of the others.
+pub(crate) fn extract_options(
+    conds: &mut Vec<Expr>,
+    streams: &mut Vec<TokenStream>,
+) -> HashMap<String, Expr> {
+    let mut options = HashMap::new();
+    let mut idx = 0;
+
+    while idx < conds.len() {
+        let name = match &conds[idx] {
+            Expr::Assign(assign) => match &*assign.left {
+                Expr::Path(path) => path.path.get_ident().map(|i| i.to_string()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match name {
+            Some(name) => {
+                if let Expr::Assign(assign) = conds.remove(idx) {
+                    options.insert(name, *assign.right);
+                }
+                streams.remove(idx);
+            }
+            None => idx += 1,
+        }
+    }
+
+    options
+}
+
 // This function rewrites a list of TokenTrees so that the "pseudooperator" for
 // implication `==>` gets transformed into an `if` expression.
 //
@@ -162,7 +198,7 @@ fn rewrite(segments: Vec<TokenTree>) -> proc_macro2::TokenStream {
 
 // The tokenstream can contain multiple expressions to be checked, separated by
 // a comma. This function "pulls" those expressions apart.
-fn segment_input(tts: TokenStream) -> Vec<Vec<TokenTree>> {
+pub(crate) fn segment_input(tts: TokenStream) -> Vec<Vec<TokenTree>> {
     let mut groups = vec![];
 
     let mut group = vec![];
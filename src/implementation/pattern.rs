@@ -0,0 +1,187 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Ident, TokenStream};
+use syn::{FnArg, ItemFn, Pat, PatType, Type};
+
+/// A name bound by the pattern of a parameter, like `a` in `(mut a, b)`.
+pub(crate) struct Binding {
+    pub(crate) ident: Ident,
+    /// The type of the binding, if the written type of the parameter tells.
+    pub(crate) ty: Option<Type>,
+    /// Whether the binding is declared `mut`, or `ref mut`.
+    pub(crate) mutable: bool,
+    /// Whether the binding is a `ref` or `ref mut` to the argument.
+    pub(crate) by_ref: bool,
+}
+
+/// All names bound by the parameter `pat_type`, from left to right.
+///
+/// A plain parameter `x: T` binds only `x`, a parameter `whole @ (a, b)`
+/// binds the whole argument and the names of its parts.
+pub(crate) fn bindings(pat_type: &PatType) -> Vec<Binding> {
+    let mut bindings = vec![];
+
+    collect(&pat_type.pat, Some(&pat_type.ty), &mut bindings);
+
+    bindings
+}
+
+fn collect(pat: &Pat, ty: Option<&Type>, bindings: &mut Vec<Binding>) {
+    // the types of the parts are only known where the written type has the
+    // same shape as the pattern
+    let ty = ty.map(ungroup);
+
+    match pat {
+        Pat::Ident(pat) => {
+            let binding_ty = ty.cloned().map(|ty| match &pat.by_ref {
+                Some(_) => {
+                    let mutability = pat.mutability;
+                    syn::parse_quote!(&#mutability #ty)
+                }
+                None => ty,
+            });
+
+            bindings.push(Binding {
+                ident: pat.ident.clone(),
+                ty: binding_ty,
+                mutable: pat.mutability.is_some(),
+                by_ref: pat.by_ref.is_some(),
+            });
+
+            // the parts of `whole @ (a, b)` have the type of `whole`
+            if let Some((_, subpat)) = &pat.subpat {
+                let ty = if pat.by_ref.is_some() { None } else { ty };
+                collect(subpat, ty, bindings);
+            }
+        }
+        Pat::Tuple(tuple) => {
+            let elems = match ty {
+                Some(Type::Tuple(ty)) if !has_rest(&tuple.elems) => {
+                    ty.elems.iter().map(Some).collect()
+                }
+                _ => vec![None; tuple.elems.len()],
+            };
+
+            for (pat, ty) in tuple.elems.iter().zip(elems) {
+                collect(pat, ty, bindings);
+            }
+        }
+        Pat::Reference(reference) => {
+            let elem = match ty {
+                Some(Type::Reference(ty)) => Some(&*ty.elem),
+                _ => None,
+            };
+
+            collect(&reference.pat, elem, bindings);
+        }
+        Pat::Slice(slice) => {
+            let elem = match ty {
+                Some(Type::Array(ty)) => Some(&*ty.elem),
+                Some(Type::Slice(ty)) => Some(&*ty.elem),
+                _ => None,
+            };
+
+            for pat in &slice.elems {
+                collect(pat, elem, bindings);
+            }
+        }
+        Pat::Struct(strukt) => {
+            for field in &strukt.fields {
+                collect(&field.pat, None, bindings);
+            }
+        }
+        Pat::TupleStruct(tuple) => {
+            for pat in &tuple.pat.elems {
+                collect(pat, None, bindings);
+            }
+        }
+        Pat::Box(boxed) => collect(&boxed.pat, None, bindings),
+        Pat::Type(typed) => collect(&typed.pat, Some(&typed.ty), bindings),
+        _ => {}
+    }
+}
+
+fn ungroup(ty: &Type) -> &Type {
+    match ty {
+        Type::Paren(ty) => ungroup(&ty.elem),
+        Type::Group(ty) => ungroup(&ty.elem),
+        ty => ty,
+    }
+}
+
+fn has_rest<'a>(pats: impl IntoIterator<Item = &'a Pat>) -> bool {
+    pats.into_iter().any(|pat| matches!(pat, Pat::Rest(_)))
+}
+
+/// The pattern that the whole argument `name` is destructured by, if it is
+/// bound like `name @ (a, b)`.
+///
+/// A follow-up run that transforms the whole argument binds its parts again
+/// from the transformed value.
+pub(crate) fn subpattern(func: &ItemFn, name: &str) -> Option<Pat> {
+    func.sig.inputs.iter().find_map(|input| match input {
+        FnArg::Typed(pat_type) => match &*pat_type.pat {
+            Pat::Ident(pat) if pat.ident == name => {
+                pat.subpat.as_ref().map(|(_, subpat)| (**subpat).clone())
+            }
+            _ => None,
+        },
+        FnArg::Receiver(_) => None,
+    })
+}
+
+/// An owned copy of an argument that a pattern destructures, built from
+/// clones of the names it binds, like `(a.clone(), b.clone())` for `(a, b)`.
+///
+/// Patterns that drop a part of the argument, like `_` or `..`, have no
+/// such copy.
+pub(crate) fn cloned(pat: &Pat) -> Option<TokenStream> {
+    match pat {
+        Pat::Ident(pat) if pat.by_ref.is_some() => {
+            let ident = &pat.ident;
+            Some(quote::quote!(::std::clone::Clone::clone(#ident)))
+        }
+        Pat::Ident(pat) => {
+            let ident = &pat.ident;
+            Some(quote::quote!(::std::clone::Clone::clone(&#ident)))
+        }
+        Pat::Tuple(tuple) => {
+            let elems =
+                tuple.elems.iter().map(cloned).collect::<Option<Vec<_>>>()?;
+
+            Some(quote::quote!((#(#elems,)*)))
+        }
+        Pat::Struct(strukt) if strukt.dot2_token.is_none() => {
+            let path = &strukt.path;
+            let fields = strukt
+                .fields
+                .iter()
+                .map(|field| {
+                    let member = &field.member;
+                    let value = cloned(&field.pat)?;
+                    Some(quote::quote!(#member: #value))
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            Some(quote::quote!(#path { #(#fields),* }))
+        }
+        Pat::TupleStruct(tuple) => {
+            let path = &tuple.path;
+            let elems = tuple
+                .pat
+                .elems
+                .iter()
+                .map(cloned)
+                .collect::<Option<Vec<_>>>()?;
+
+            Some(quote::quote!(#path(#(#elems),*)))
+        }
+        Pat::Box(boxed) => {
+            let value = cloned(&boxed.pat)?;
+            Some(quote::quote!(::std::boxed::Box::new(#value)))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,258 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::{
+    parse::{ParseStream, Parser},
+    punctuated::Punctuated,
+    Attribute, Expr, Ident,
+};
+
+/// A parsed `mr_sequence!` invocation.
+struct Sequence {
+    /// Attributes for the generated test functions.
+    attrs: Vec<Attribute>,
+    /// Prefix of the generated test functions.
+    name: Ident,
+    /// Creates a fresh value for each order of calls.
+    setup: Expr,
+    /// The variable the inputs are bound to and the inputs.
+    inputs: Option<(Ident, Expr)>,
+    /// The calls that may be made in any order.
+    independent: Vec<Call>,
+}
+
+/// A method call on the value created by the setup.
+struct Call {
+    method: Ident,
+    args: Vec<Expr>,
+}
+
+/// Generate a test for every pair of independent calls, which checks that
+/// both calls return the same values no matter which one is made first.
+pub(crate) fn mr_sequence(toks: TokenStream) -> TokenStream {
+    let sequence = match parse_sequence.parse2(toks) {
+        Ok(sequence) => sequence,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let mut tests = TokenStream::new();
+
+    let calls = sequence.independent.iter().enumerate();
+
+    for (idx, first) in calls.clone() {
+        for second in calls.clone().skip(idx + 1) {
+            tests.extend(pair_test(&sequence, (idx, first), second));
+        }
+    }
+
+    tests
+}
+
+fn parse_sequence(input: ParseStream) -> syn::Result<Sequence> {
+    let attrs = input.call(Attribute::parse_outer)?;
+
+    let mut name = None;
+    let mut setup = None;
+    let mut inputs = None;
+    let mut independent = None;
+
+    while !input.is_empty() {
+        let key: Ident = input.parse()?;
+        input.parse::<syn::Token![:]>()?;
+
+        match key.to_string().as_str() {
+            "name" => name = Some(input.parse()?),
+            "setup" => setup = Some(input.parse()?),
+            "inputs" => {
+                let var: Ident = input.parse()?;
+                input.parse::<syn::Token![in]>()?;
+                inputs = Some((var, input.parse()?));
+            }
+            "independent" => {
+                let content;
+                syn::bracketed!(content in input);
+                let calls =
+                    Punctuated::<Expr, syn::Token![,]>::parse_terminated(
+                        &content,
+                    )?;
+
+                independent = Some(
+                    calls
+                        .into_iter()
+                        .map(parse_call)
+                        .collect::<syn::Result<Vec<_>>>()?,
+                );
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "expected `name`, `setup`, `inputs` or `independent`",
+                ))
+            }
+        }
+
+        if !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+        }
+    }
+
+    let setup = setup.ok_or_else(|| {
+        syn::Error::new(Span::call_site(), "mr_sequence! needs a `setup`")
+    })?;
+    let independent = independent.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "mr_sequence! needs the `independent` calls",
+        )
+    })?;
+
+    Ok(Sequence {
+        attrs,
+        name: name
+            .unwrap_or_else(|| Ident::new("mr_sequence", Span::call_site())),
+        setup,
+        inputs,
+        independent,
+    })
+}
+
+/// Calls are written as `method` or `method(args...)`.
+fn parse_call(expr: Expr) -> syn::Result<Call> {
+    let method = |path: &Expr| match path {
+        Expr::Path(path) => path.path.get_ident().cloned(),
+        _ => None,
+    };
+
+    let call = match &expr {
+        Expr::Call(call) => method(&call.func).map(|method| Call {
+            method,
+            args: call.args.iter().cloned().collect(),
+        }),
+        path => method(path).map(|method| Call {
+            method,
+            args: vec![],
+        }),
+    };
+
+    call.ok_or_else(|| {
+        syn::Error::new_spanned(
+            expr,
+            "expected the name of a method, optionally with arguments",
+        )
+    })
+}
+
+/// The test for the calls at positions `first.0` and `second.0`, the
+/// positions keep the names unique if a method is listed more than once.
+fn pair_test(
+    sequence: &Sequence,
+    (first_idx, first): (usize, &Call),
+    (second_idx, second): (usize, &Call),
+) -> TokenStream {
+    let span = Span::call_site();
+    let test_name = Ident::new(
+        &format!(
+            "{}_{}_{}_{}_{}",
+            sequence.name, first.method, first_idx, second.method, second_idx
+        ),
+        span,
+    );
+
+    // a setup ending in `?` may fail, those inputs are skipped
+    let skip = if sequence.inputs.is_some() {
+        quote::quote!(continue)
+    } else {
+        quote::quote!(return)
+    };
+    let setup = match &sequence.setup {
+        Expr::Try(setup) => {
+            let setup = &setup.expr;
+            quote::quote! {
+                match ::std::iter::IntoIterator::into_iter(#setup).next() {
+                    Some(value) => value,
+                    None => #skip,
+                }
+            }
+        }
+        setup => setup.into_token_stream(),
+    };
+
+    let call = |value: &Ident, call: &Call| {
+        let method = &call.method;
+        let args = &call.args;
+        quote::quote!(#value.#method(#(#args),*))
+    };
+
+    let value_before = Ident::new("__contract_first_then_second", span);
+    let value_after = Ident::new("__contract_second_then_first", span);
+
+    let first_before = call(&value_before, first);
+    let second_after = call(&value_before, second);
+    let second_before = call(&value_after, second);
+    let first_after = call(&value_after, first);
+
+    let input = match &sequence.inputs {
+        Some((var, _)) => quote::quote!(format!(" for input {:?}", #var)),
+        None => quote::quote!(""),
+    };
+
+    let message = |call: &Call, other: &Call| {
+        format!(
+            "`{}` returns {{:?}} when called before `{}`, but {{:?}} when \
+             called after it{{}}",
+            call.method, other.method
+        )
+    };
+    let first_message = message(first, second);
+    let second_message = message(second, first);
+
+    let body = quote::quote! {
+        #[allow(unused_mut)]
+        let mut #value_before = #setup;
+        #[allow(unused_mut)]
+        let mut #value_after = #setup;
+
+        let first_before = #first_before;
+        let second_after = #second_after;
+
+        let second_before = #second_before;
+        let first_after = #first_after;
+
+        assert!(
+            first_before == first_after,
+            #first_message,
+            first_before,
+            first_after,
+            #input
+        );
+        assert!(
+            second_before == second_after,
+            #second_message,
+            second_before,
+            second_after,
+            #input
+        );
+    };
+
+    let body = match &sequence.inputs {
+        Some((var, inputs)) => quote::quote! {
+            for #var in #inputs {
+                #body
+            }
+        },
+        None => body,
+    };
+
+    let attrs = &sequence.attrs;
+
+    quote::quote! {
+        #[test]
+        #(#attrs)*
+        fn #test_name() {
+            #body
+        }
+    }
+}
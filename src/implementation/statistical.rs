@@ -0,0 +1,425 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{FnArg, Ident, ItemFn, Pat, ReturnType, Type};
+
+use crate::implementation::{
+    codegen, equivalent_to, mr_seed, pattern, Contract, ContractMode,
+    ContractType, FuncWithContracts,
+};
+
+/// Number of samples of each run if the relation does not name it.
+const SAMPLES: usize = 1000;
+
+/// Significance level if the relation does not name it.
+const ALPHA: f64 = 0.01;
+
+/// Relation if none is named.
+const MEAN_EQ: &str = "mean_eq";
+
+/// Relation that compares the whole distributions.
+const DIST_EQ: &str = "dist_eq";
+
+pub(crate) fn statistical(
+    mode: ContractMode,
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let ty = ContractType::Statistical;
+
+    let func = syn::parse_quote!(#toks);
+
+    let f = FuncWithContracts::new_with_initial_contract(func, ty, mode, attr);
+
+    f.generate()
+}
+
+/// The name of the relation between the two distributions.
+pub(crate) fn relation(contract: &Contract) -> String {
+    contract.option("relation").map_or_else(
+        || String::from(MEAN_EQ),
+        |relation| relation.to_token_stream().to_string(),
+    )
+}
+
+/// The transformed variable, written with the transformation applied.
+pub(crate) fn follow_up(contract: &Contract) -> String {
+    let arg =
+        |idx: usize| contract.streams.get(idx).cloned().unwrap_or_default();
+
+    let (key, op, modi) = (arg(0), arg(1), arg(2));
+
+    match op.clone().into_iter().next() {
+        Some(TokenTree::Ident(method)) => {
+            format!("{}.{}({})", key, method, modi)
+        }
+        _ => format!("{} {} {}", key, op, modi),
+    }
+}
+
+/// Generate the sampling of the function on the original and on the
+/// transformed input and the test of the relation between both samples,
+/// which are placed before the function body.
+///
+/// The body is inlined for every sample. Shared references are passed on,
+/// owned values, `self` and the values behind `&mut` parameters are cloned,
+/// so the arguments of the caller are left as they are. The random number
+/// generators in `seeded` are copied once, and the copy advances through the
+/// original and then the transformed samples, so both samples are drawn
+/// independently of each other.
+pub(crate) fn samples(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+    seeded: &[Ident],
+) -> TokenStream {
+    match try_samples(contract, index, func, seeded) {
+        Ok(toks) => toks,
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+fn try_samples(
+    contract: &Contract,
+    index: usize,
+    func: &ItemFn,
+    seeded: &[Ident],
+) -> Result<TokenStream, syn::Error> {
+    let mode = contract.mode.final_mode();
+
+    let cfg = match equivalent_to::mode_cfg(mode) {
+        Some(cfg) => cfg,
+        None => return Ok(TokenStream::new()),
+    };
+
+    let (key, op, modi) = match contract.streams.as_slice() {
+        [key, op, modi] => (syn::parse2::<Ident>(key.clone())?, op, modi),
+        _ => {
+            let msg = "#[statistical] needs the transformed parameter, the \
+                       operator and the modifier";
+            return Err(syn::Error::new(Span::call_site(), msg));
+        }
+    };
+
+    let relation = relation(contract);
+    let test = match relation.as_str() {
+        MEAN_EQ => quote::quote!(__contract_mean_eq),
+        DIST_EQ => quote::quote!(__contract_dist_eq),
+        _ => {
+            let msg = format!(
+                "unknown relation `{}`, expected `{}` or `{}`",
+                relation, MEAN_EQ, DIST_EQ
+            );
+            return Err(syn::Error::new_spanned(
+                contract.option("relation"),
+                msg,
+            ));
+        }
+    };
+
+    let samples = match contract.option("samples") {
+        Some(samples) => samples.to_token_stream(),
+        None => quote::quote!(#SAMPLES),
+    };
+    let alpha = match contract.option("alpha") {
+        Some(alpha) => alpha.to_token_stream(),
+        None => quote::quote!(#ALPHA),
+    };
+
+    let state_ident = |name: &str| {
+        let name = format!("__contract_statistical_{}_{}", index, name);
+        Ident::new(&name, Span::call_site())
+    };
+
+    let func_name = func.sig.ident.to_string();
+    let self_clone = state_ident("self");
+
+    let mut block = syn::parse2::<syn::Expr>(func.block.to_token_stream())?;
+    let mut self_binding = TokenStream::new();
+    let mut params = vec![];
+    let mut generators = vec![];
+
+    for input in &func.sig.inputs {
+        match input {
+            FnArg::Receiver(receiver) => {
+                // a method that changes `self` runs on a clone of it
+                let self_ref = match (&receiver.reference, receiver.mutability)
+                {
+                    (Some(_), None) => continue,
+                    (Some(_), Some(_)) => quote::quote!(&*self),
+                    (None, _) => quote::quote!(&self),
+                };
+
+                block = codegen::rename_bindings(
+                    &block,
+                    &[(String::from("self"), self_clone.to_string())],
+                );
+                self_binding = quote::quote! {
+                    #[allow(unused_mut)]
+                    let mut #self_clone = ::std::clone::Clone::clone(#self_ref);
+                };
+            }
+            FnArg::Typed(pat_type) => {
+                let ident = match &*pat_type.pat {
+                    Pat::Ident(pat)
+                        if pat.by_ref.is_none() && pat.subpat.is_none() =>
+                    {
+                        &pat.ident
+                    }
+                    // the names bound by the pattern of an owned argument are
+                    // passed on one by one
+                    _ if !matches!(*pat_type.ty, Type::Reference(_)) => {
+                        for binding in pattern::bindings(pat_type) {
+                            let ident = binding.ident;
+                            let value = if binding.by_ref {
+                                ident.to_token_stream()
+                            } else {
+                                quote::quote!(::std::clone::Clone::clone(&#ident))
+                            };
+                            params.push((ident, value));
+                        }
+                        continue;
+                    }
+                    pat => {
+                        let msg = "#[statistical] needs named parameters";
+                        return Err(syn::Error::new_spanned(pat, msg));
+                    }
+                };
+
+                let value = match &*pat_type.ty {
+                    Type::Reference(ty)
+                        if ty.mutability.is_some()
+                            && seeded.contains(ident) =>
+                    {
+                        let state = state_ident(&format!("rng_{}", ident));
+                        let fresh = mr_seed::fresh(quote::quote!(&*#ident));
+                        generators.push(quote::quote! {
+                            let mut #state = #fresh;
+                        });
+                        quote::quote!(&mut #state)
+                    }
+                    // a clone of a generator would draw the same numbers for
+                    // every sample
+                    Type::Reference(_) if mr_seed::looks_like_rng(pat_type) => {
+                        let msg = format!(
+                            "`{}` looks like a random number generator, mark \
+                             it with #[mr_seed] to draw new numbers for each \
+                             sample",
+                            ident
+                        );
+                        return Err(syn::Error::new_spanned(pat_type, msg));
+                    }
+                    Type::Reference(ty) if ty.mutability.is_some() => {
+                        quote::quote!(&mut ::std::clone::Clone::clone(&*#ident))
+                    }
+                    Type::Reference(_) => ident.to_token_stream(),
+                    _ => quote::quote!(::std::clone::Clone::clone(&#ident)),
+                };
+
+                params.push((ident.clone(), value));
+            }
+        }
+    }
+
+    if !params.iter().any(|(ident, _)| *ident == key) {
+        let msg = format!("`{}` is not a parameter of `{}`", key, func_name);
+        return Err(syn::Error::new_spanned(key, msg));
+    }
+
+    // the source run passes the variable on, the follow-up run applies the
+    // operator to it
+    let bindings = |transform: bool| {
+        let values = params.iter().map(|(ident, value)| {
+            if !transform || *ident != key {
+                return value.clone();
+            }
+
+            match op.clone().into_iter().next() {
+                Some(TokenTree::Ident(method)) => {
+                    quote::quote!((#value).#method(#modi))
+                }
+                _ => quote::quote!((#value) #op #modi),
+            }
+        });
+        let idents = params.iter().map(|(ident, _)| ident);
+
+        quote::quote! {
+            #self_binding
+            #(
+                #[allow(unused_mut)]
+                let mut #idents = #values;
+            )*
+        }
+    };
+
+    let ret_ty = match &func.sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::ImplTrait(_) => TokenStream::new(),
+            ty => quote::quote!(-> #ty),
+        },
+        ReturnType::Default => TokenStream::new(),
+    };
+
+    let ret = state_ident("ret");
+    let sample = match contract.option("on") {
+        Some(on) => codegen::select_on(on, &ret),
+        None => ret.to_token_stream(),
+    };
+
+    let run = |transform: bool| {
+        let bindings = bindings(transform);
+
+        quote::quote! {
+            {
+                (0..#samples)
+                    .map(|_| {
+                        #bindings
+                        #[allow(unused_mut)]
+                        let mut run = || #ret_ty #block;
+                        let #ret = run();
+                        (#sample) as f64
+                    })
+                    .collect::<::std::vec::Vec<f64>>()
+            }
+        }
+    };
+
+    let source_values = state_ident("source");
+    let follow_up_values = state_ident("follow_up");
+    let p_value = state_ident("p_value");
+
+    let run_source = run(false);
+    let run_follow_up = run(true);
+
+    let message = format!(
+        "{}: `{}` of {{}} samples of `{}` with `{}` and `{}` has a p-value of \
+         {{:?}}, below the significance level of {}",
+        equivalent_to::escape(&equivalent_to::violation(contract, &func_name)),
+        relation,
+        func_name,
+        key,
+        equivalent_to::escape(&follow_up(contract)),
+        equivalent_to::escape(&alpha.to_string()),
+    );
+
+    let span = Span::call_site();
+    let check = match codegen::get_assert_macro(
+        ContractType::Ensures,
+        mode,
+        span,
+    ) {
+        Some(assert_macro) => quote::quote! {
+            #assert_macro!(#p_value >= #alpha, #message, #samples, #p_value);
+        },
+        None => TokenStream::new(),
+    };
+    let log = if mode == ContractMode::LogOnly {
+        quote::quote! {
+            if #p_value < #alpha {
+                log::error!(#message, #samples, #p_value);
+            }
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    let tests = tests();
+    let helpers = if generators.is_empty() {
+        TokenStream::new()
+    } else {
+        mr_seed::helpers(mr_seed::uses_rand(func, seeded))
+    };
+
+    Ok(quote::quote! {
+        #cfg
+        {
+            #tests
+            #helpers
+
+            #(#generators)*
+            let #source_values = #run_source;
+            let #follow_up_values = #run_follow_up;
+            let #p_value = #test(&#source_values, &#follow_up_values);
+
+            #log
+            #check
+        }
+    })
+}
+
+/// The tests of the relations, which compute the p-value of both samples
+/// being drawn from distributions with equal means or equal in general.
+fn tests() -> TokenStream {
+    quote::quote! {
+        // Welch's t-test, with the normal approximation of the t-distribution
+        // for the large number of samples
+        #[allow(dead_code)]
+        fn __contract_mean_eq(a: &[f64], b: &[f64]) -> f64 {
+            fn moments(values: &[f64]) -> (f64, f64) {
+                let n = values.len() as f64;
+                let mean = values.iter().sum::<f64>() / n;
+                let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>()
+                    / (n - 1.0);
+                (mean, var / n)
+            }
+
+            let ((mean_a, err_a), (mean_b, err_b)) = (moments(a), moments(b));
+            if err_a + err_b == 0.0 {
+                return if mean_a == mean_b { 1.0 } else { 0.0 };
+            }
+
+            let z = (mean_a - mean_b).abs() / (err_a + err_b).sqrt();
+
+            // erfc(z / sqrt(2)) after Abramowitz and Stegun 7.1.26
+            let x = z / ::std::f64::consts::SQRT_2;
+            let t = 1.0 / (1.0 + 0.3275911 * x);
+            let poly = t
+                * (0.254829592
+                    + t * (-0.284496736
+                        + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+            poly * (-x * x).exp()
+        }
+
+        // the two-sample Kolmogorov-Smirnov test with the asymptotic
+        // distribution of its statistic
+        #[allow(dead_code)]
+        fn __contract_dist_eq(a: &[f64], b: &[f64]) -> f64 {
+            let sorted = |values: &[f64]| {
+                let mut values = values.to_vec();
+                values.sort_by(|x, y| x.partial_cmp(y).unwrap_or(::std::cmp::Ordering::Equal));
+                values
+            };
+            let (a, b) = (sorted(a), sorted(b));
+            let (n, m) = (a.len() as f64, b.len() as f64);
+
+            let (mut i, mut j, mut d) = (0, 0, 0.0f64);
+            while i < a.len() && j < b.len() {
+                let value = a[i].min(b[j]);
+                while i < a.len() && a[i] <= value {
+                    i += 1;
+                }
+                while j < b.len() && b[j] <= value {
+                    j += 1;
+                }
+                d = d.max((i as f64 / n - j as f64 / m).abs());
+            }
+
+            let en = (n * m / (n + m)).sqrt();
+            let lambda = (en + 0.12 + 0.11 / en) * d;
+            if lambda < 0.2 {
+                return 1.0;
+            }
+
+            let mut p = 0.0;
+            for k in 1..=100 {
+                let k = k as f64;
+                let sign = if k as u64 % 2 == 1 { 2.0 } else { -2.0 };
+                p += sign * (-2.0 * k * k * lambda * lambda).exp();
+            }
+            p.max(0.0).min(1.0)
+        }
+    }
+}
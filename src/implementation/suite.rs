@@ -0,0 +1,284 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{
+    parse::{ParseStream, Parser},
+    Attribute, Item, ItemFn, LitStr,
+};
+
+use crate::implementation::{
+    Contract, ContractMode, ContractType, FuncWithContracts,
+};
+
+/// Name of the macro that holds the members of the suite `name`.
+fn suite_macro_name(name: &syn::Ident) -> syn::Ident {
+    syn::Ident::new(&format!("__mr_suite_{}", name), name.span())
+}
+
+/// Path of the macro that holds the members of the suite at `path`, like
+/// `suites::__mr_suite_linear` for `suites::linear`.
+fn suite_macro_path(path: &syn::Path) -> syn::Path {
+    let mut path = path.clone();
+
+    if let Some(last) = path.segments.last_mut() {
+        last.ident = suite_macro_name(&last.ident);
+    }
+
+    path
+}
+
+/// Define a suite of relations that can be applied to a parameter at once.
+///
+/// A suite only exists as tokens in the crate that uses it, so a
+/// `macro_rules!` macro which knows the members is created. `#[mr_suite(name,
+/// x)]` calls that macro, which applies the resolved form
+/// `#[mr_suite("members", x)]` to the item again. The macro is also exported
+/// to the rest of the crate with `pub(crate) use`, so other modules can name
+/// the suite by its path.
+pub(crate) fn define_mr_suite(toks: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream| {
+        let name: syn::Ident = input.parse()?;
+        input.parse::<syn::Token![,]>()?;
+        let content;
+        syn::bracketed!(content in input);
+        let members: TokenStream = content.parse()?;
+        Ok((name, members))
+    };
+
+    let (name, members) = match parser.parse2(toks) {
+        Ok(suite) => suite,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    // report unknown relations where the suite is defined
+    if let Err(err) = parse_members(members.clone()) {
+        return err.to_compile_error();
+    }
+
+    let macro_name = suite_macro_name(&name);
+    let members = LitStr::new(&members.to_string(), Span::call_site());
+
+    quote::quote! {
+        #[allow(unused_macros)]
+        macro_rules! #macro_name {
+            ([$($para:tt)*] $($item:tt)*) => {
+                #[mr_suite(#members, $($para)*)]
+                $($item)*
+            };
+        }
+
+        #[allow(unused_imports)]
+        pub(crate) use #macro_name;
+    }
+}
+
+pub(crate) fn mr_suite(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let (suite, para) = match suite_args.parse2(attr) {
+        Ok(args) => args,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let members = match suite {
+        Suite::Named(path) => {
+            let macro_path = suite_macro_path(&path);
+
+            return quote::quote! {
+                #macro_path! { [#para] #toks }
+            };
+        }
+        Suite::Members(members) => members,
+    };
+
+    let mut func: ItemFn = match syn::parse2::<Item>(toks) {
+        Ok(Item::Fn(func)) => func,
+        _ => {
+            unimplemented!("The #[mr_suite] attribute only works on functions.")
+        }
+    };
+
+    // another suite that is not resolved yet has to expand first, so this
+    // one is applied again below it
+    if let Some(idx) = func.attrs.iter().position(is_named_suite) {
+        let members = LitStr::new(&members.to_string(), Span::call_site());
+
+        func.attrs
+            .insert(idx + 1, syn::parse_quote!(#[mr_suite(#members, #para)]));
+
+        return func.into_token_stream();
+    }
+
+    let contracts = match suite_contracts(members, &para) {
+        Ok(contracts) => contracts,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let toks =
+        FuncWithContracts::new_with_contracts(func, contracts).generate();
+
+    // This attribute was applied by the macro of the suite, so names created
+    // at the call site would resolve inside of that macro, while the names
+    // written by the user don't. All names are resolved in the same context
+    // instead, which still belongs to this expansion.
+    respan(toks, &|span| span.resolved_at(Span::mixed_site()))
+}
+
+/// The span of the parameter a suite is applied to, which was written by the
+/// user.
+fn para_span(para: &TokenStream) -> Span {
+    para.clone()
+        .into_iter()
+        .next()
+        .map_or_else(Span::call_site, |tt| tt.span())
+}
+
+/// The two forms of the first argument of `#[mr_suite]`.
+enum Suite {
+    /// The name or the path of a suite from `define_mr_suite!`.
+    Named(syn::Path),
+    /// The members of a suite, written as a string.
+    Members(TokenStream),
+}
+
+fn suite_args(input: ParseStream) -> syn::Result<(Suite, TokenStream)> {
+    let suite = if input.peek(LitStr) {
+        let members: LitStr = input.parse()?;
+        Suite::Members(members.parse()?)
+    } else {
+        Suite::Named(input.parse()?)
+    };
+    input.parse::<syn::Token![,]>()?;
+    let para: TokenStream = input.parse()?;
+
+    Ok((suite, para))
+}
+
+/// Whether `attr` is a `#[mr_suite]` attribute.
+pub(crate) fn is_mr_suite(attr: &Attribute) -> bool {
+    attr.path.segments.last().unwrap().ident == "mr_suite"
+}
+
+/// Whether `attr` is a `#[mr_suite]` attribute that only names its suite.
+pub(crate) fn is_named_suite(attr: &Attribute) -> bool {
+    is_mr_suite(attr)
+        && matches!(attr.parse_args_with(suite_args), Ok((Suite::Named(_), _)))
+}
+
+/// The contracts of a resolved `#[mr_suite]` attribute.
+pub(crate) fn attribute_contracts(attr: &Attribute) -> Vec<Contract> {
+    let result =
+        attr.parse_args_with(suite_args).and_then(
+            |(suite, para)| match suite {
+                Suite::Members(members) => suite_contracts(members, &para),
+                Suite::Named(path) => Err(syn::Error::new_spanned(
+                    path,
+                    "#[mr_suite] has to be placed before the other contract \
+                 attributes",
+                )),
+            },
+        );
+
+    match result {
+        Ok(contracts) => contracts,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+/// Apply all members of a suite to the parameter `para`.
+fn suite_contracts(
+    members: TokenStream,
+    para: &TokenStream,
+) -> Result<Vec<Contract>, syn::Error> {
+    // the members were passed through a string, errors in them are reported
+    // at the parameter
+    let site = para_span(para);
+
+    let contracts = parse_members(respan(members, &|_| site))?
+        .into_iter()
+        .map(|(ty, mode, args)| {
+            let toks = if args.is_empty() {
+                para.clone()
+            } else {
+                quote::quote!(#para, #args)
+            };
+
+            Contract::from_toks(ty, mode, toks)
+        })
+        .collect();
+
+    Ok(contracts)
+}
+
+/// Parse a list of relations like `homomorphism(+, 1), symmetry(-, 0, -)`.
+fn parse_members(
+    members: TokenStream,
+) -> Result<Vec<(ContractType, ContractMode, TokenStream)>, syn::Error> {
+    let mut parsed = vec![];
+    let mut tokens = members.into_iter().peekable();
+
+    while let Some(tt) = tokens.next() {
+        let name = match tt {
+            TokenTree::Ident(name) => name,
+            tt => {
+                return Err(syn::Error::new(
+                    tt.span(),
+                    "expected the name of a relation",
+                ))
+            }
+        };
+
+        let (ty, mode) =
+            ContractType::contract_type_and_mode(&name.to_string())
+                .filter(|(ty, _)| ty.is_metamorphic())
+                .ok_or_else(|| {
+                    syn::Error::new(
+                        name.span(),
+                        format!("`{}` is not a metamorphic relation", name),
+                    )
+                })?;
+
+        let args = match tokens.peek() {
+            Some(TokenTree::Group(group)) => {
+                let args = group.stream();
+                tokens.next();
+                args
+            }
+            _ => TokenStream::new(),
+        };
+
+        parsed.push((ty, mode, args));
+
+        match tokens.next() {
+            None => break,
+            Some(TokenTree::Punct(p)) if p.as_char() == ',' => {}
+            Some(tt) => {
+                return Err(syn::Error::new(
+                    tt.span(),
+                    "relations of a suite are separated by commas",
+                ))
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Change the spans of all tokens in `stream`.
+fn respan(stream: TokenStream, span: &dyn Fn(Span) -> Span) -> TokenStream {
+    stream
+        .into_iter()
+        .map(|mut tt| {
+            if let TokenTree::Group(group) = &tt {
+                let mut new_group =
+                    Group::new(group.delimiter(), respan(group.stream(), span));
+                new_group.set_span(span(group.span()));
+                tt = TokenTree::Group(new_group);
+            } else {
+                tt.set_span(span(tt.span()));
+            }
+            tt
+        })
+        .collect()
+}
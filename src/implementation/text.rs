@@ -0,0 +1,150 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Delimiter, Ident, TokenStream, TokenTree};
+use quote::ToTokens;
+
+/// Names of the built-in transformations of text, which relations name as
+/// their operator, like `#[periodicity(host, lowercase)]`, or call with their
+/// modifier, like `#[periodicity(host, pad("\t"))]`.
+pub(crate) const TRANSFORMS: &[&str] = &[
+    "lowercase",
+    "uppercase",
+    "trim",
+    "pad",
+    "percent_encode",
+    "nfc",
+    "nfd",
+    "insert",
+];
+
+/// The built-in transformation that `op` names, if any, and the modifier
+/// that it is called with.
+pub(crate) fn transform_name(op: &TokenStream) -> Option<(Ident, TokenStream)> {
+    let mut tokens = op.clone().into_iter();
+
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(name))
+            if TRANSFORMS.contains(&name.to_string().as_str()) =>
+        {
+            name
+        }
+        _ => return None,
+    };
+
+    match (tokens.next(), tokens.next()) {
+        (None, _) => Some((name, TokenStream::new())),
+        (Some(TokenTree::Group(args)), None)
+            if args.delimiter() == Delimiter::Parenthesis =>
+        {
+            // several arguments are passed on as a tuple, like `insert` takes
+            let is_tuple = args.stream().into_iter().any(
+                |tt| matches!(tt, TokenTree::Punct(p) if p.as_char() == ','),
+            );
+            let args = if is_tuple {
+                args.to_token_stream()
+            } else {
+                args.stream()
+            };
+
+            Some((name, args))
+        }
+        _ => None,
+    }
+}
+
+/// Bind `target` to the text of `source` changed by the transformation `op`,
+/// if `op` names one and the parameter is a `String`.
+///
+/// A `&str` parameter is transformed as an owned `String` as well, which the
+/// follow-up run borrows again.
+pub(crate) fn follow_up(
+    ident_type: &str,
+    source: &Ident,
+    modi: &TokenStream,
+    op: &TokenStream,
+    mut_def: &TokenStream,
+    target: &Ident,
+) -> Option<TokenStream> {
+    if ident_type != "String" {
+        return None;
+    }
+
+    let (name, args) = transform_name(op)?;
+    let modi = if args.is_empty() { modi } else { &args };
+    let owned = owned(&name, &source.to_token_stream(), modi);
+
+    Some(quote::quote! {
+        let #mut_def #target = #owned;
+    })
+}
+
+/// The expression that computes the text of `source` changed by the
+/// transformation `name` as a `String`.
+pub(crate) fn owned(
+    name: &Ident,
+    source: &TokenStream,
+    modi: &TokenStream,
+) -> TokenStream {
+    let change = match name.to_string().as_str() {
+        "lowercase" => quote::quote!(text.to_lowercase()),
+        "uppercase" => quote::quote!(text.to_uppercase()),
+        "trim" => quote::quote!(text.trim().to_owned()),
+        // surrounds the text with whitespace, a single space by default
+        "pad" => {
+            let space = if modi.is_empty() {
+                quote::quote!(" ")
+            } else {
+                modi.clone()
+            };
+
+            quote::quote! {{
+                let space: &str = #space;
+                format!("{}{}{}", space, text, space)
+            }}
+        }
+        // encodes every byte that is not unreserved in URLs (RFC 3986)
+        "percent_encode" => quote::quote! {
+            text.bytes()
+                .map(|byte| match byte {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        ::std::string::String::from(byte as char)
+                    }
+                    _ => format!("%{:02X}", byte),
+                })
+                .collect::<::std::string::String>()
+        },
+        "nfc" | "nfd" if !cfg!(feature = "unicode_normalization") => {
+            let msg = format!(
+                "`{}` needs the `unicode_normalization` feature of contracts \
+                 and a dependency on `unicode-normalization`",
+                name
+            );
+            return syn::Error::new_spanned(name, msg).to_compile_error();
+        }
+        "nfc" => quote::quote! {
+            ::unicode_normalization::UnicodeNormalization::nfc(text)
+                .collect::<::std::string::String>()
+        },
+        "nfd" => quote::quote! {
+            ::unicode_normalization::UnicodeNormalization::nfd(text)
+                .collect::<::std::string::String>()
+        },
+        // inserts a substring at a character index, or at the end if the
+        // text is shorter
+        "insert" => quote::quote! {{
+            let (at, substring): (usize, &str) = #modi;
+            let at = text.char_indices().nth(at).map_or(text.len(), |(at, _)| at);
+            let mut changed = text.to_owned();
+            changed.insert_str(at, substring);
+            changed
+        }},
+        _ => unreachable!("not a text transformation"),
+    };
+
+    quote::quote! {{
+        let text: &str = ::std::convert::AsRef::<str>::as_ref(&#source);
+        #change
+    }}
+}
@@ -6,7 +6,7 @@ use crate::implementation::ContractType;
 use proc_macro2::TokenStream;
 use quote::ToTokens;
 use syn::{
-    FnArg, ImplItem, ItemImpl, ItemTrait, Pat, TraitItem, TraitItemMethod,
+    FnArg, ImplItem, ItemImpl, ItemTrait, Pat, TraitItem, TraitItemMethod, Type,
 };
 
 /// Name used for the "re-routed" method.
@@ -99,21 +99,91 @@ pub(crate) fn contract_trait_item_trait(
 
         let mut m: TraitItemMethod = (*method).clone();
 
+        // Metamorphic relations execute the body again for their follow-up
+        // runs, which here is the call to the implementation. Owned
+        // arguments are cloned for every call, so they are still available
+        // after the first one. Mutable references are passed as reborrows,
+        // a follow-up run replaces them with an owned clone which is then
+        // borrowed for the call.
+        let has_relation = m.attrs.iter().any(|a| {
+            let name = a.path.segments.last().unwrap().ident.to_string();
+
+            matches!(
+                ContractType::contract_type_and_mode(&name),
+                Some((ty, _)) if ty.is_metamorphic()
+            )
+        });
+
         let argument_data = m
             .sig
             .inputs
             .clone()
             .into_iter()
             .map(|t: FnArg| match &t {
+                FnArg::Receiver(r) if has_relation && r.reference.is_none() => {
+                    quote::quote!(self.clone())
+                }
+                FnArg::Receiver(r)
+                    if has_relation && r.mutability.is_some() =>
+                {
+                    quote::quote!(&mut *self)
+                }
                 FnArg::Receiver(_) => quote::quote!(self),
                 FnArg::Typed(p) => {
                     let info = arg_pat_info(&p.pat);
+                    let call_toks = info.call_toks;
 
-                    info.call_toks
+                    match &*p.ty {
+                        Type::Reference(r)
+                            if has_relation && r.mutability.is_some() =>
+                        {
+                            quote::quote!(&mut *#call_toks)
+                        }
+                        Type::Reference(_) => call_toks,
+                        _ if has_relation => quote::quote!(#call_toks.clone()),
+                        _ => call_toks,
+                    }
                 }
             })
             .collect::<Vec<_>>();
 
+        // cloning `self` or `*self` for a follow-up run is only possible if
+        // the implementor is `Clone`
+        let self_cloned = m.sig.inputs.iter().any(|arg| {
+            matches!(
+                arg,
+                FnArg::Receiver(r) if r.reference.is_none() || r.mutability.is_some()
+            )
+        });
+        if has_relation && self_cloned {
+            m.sig
+                .generics
+                .make_where_clause()
+                .predicates
+                .push(syn::parse_quote!(Self: Clone));
+        }
+
+        // the same goes for owned arguments of a generic type of the method,
+        // which get the bound next to their other ones, other types are
+        // checked where the trait is defined
+        let cloned_generics = m
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|arg| match arg {
+                FnArg::Typed(p) if has_relation => match &*p.ty {
+                    Type::Path(path) => path.path.get_ident().cloned(),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for param in m.sig.generics.type_params_mut() {
+            if cloned_generics.contains(&param.ident) {
+                param.bounds.push(syn::parse_quote!(Clone));
+            }
+        }
+
         let arguments = {
             let mut toks = proc_macro2::TokenStream::new();
 
@@ -190,11 +260,9 @@ pub(crate) fn contract_trait_item_trait(
         .collect::<Vec<_>>();
 
     // remove all previous methods
-    trait_.items = trait_
+    trait_
         .items
-        .into_iter()
-        .filter(|item| !matches!(item, TraitItem::Method(_)))
-        .collect();
+        .retain(|item| !matches!(item, TraitItem::Method(_)));
 
     // add back new methods
     trait_.items.extend(funcs);
@@ -269,6 +337,117 @@ mod tests {
         assert_eq!(generated.to_string(), expected.to_string());
     }
 
+    #[test]
+    fn relations_clone_owned_arguments() {
+        // every follow-up run of a relation calls the implementation again,
+        // so owned arguments can't be moved into the first call.
+
+        let code = syn::parse_quote! {
+            trait Shape {
+                #[periodicity(turns, +, 1)]
+                fn angle(self, name: String, turns: u32, unit: &str) -> u32;
+            }
+        };
+
+        let expected = quote::quote! {
+            trait Shape {
+                #[doc(hidden)]
+                #[doc = "This is an internal function that is not meant to be used directly!"]
+                #[doc = "See the documentation of the `#[contract_trait]` attribute."]
+                fn __contracts_impl_angle(self, name: String, turns: u32, unit: &str) -> u32;
+
+                #[periodicity(turns, +, 1)]
+                #[inline(always)]
+                fn angle(self, name: String, turns: u32, unit: &str) -> u32
+                where
+                    Self: Clone
+                {
+                    Self::__contracts_impl_angle(self.clone(), name.clone(), turns.clone(), unit,)
+                }
+            }
+        };
+
+        let generated =
+            super::contract_trait_item_trait(Default::default(), code);
+
+        assert_eq!(generated.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn only_relations_that_clone_self_need_clone() {
+        // methods without relations and relations on `&self` methods leave
+        // the arguments and the bounds of the method as they are.
+
+        let code = syn::parse_quote! {
+            trait Wheel {
+                #[periodicity(base, +, 10)]
+                fn digit(&self, base: u32) -> u32;
+
+                fn reset(&mut self, position: u32);
+            }
+        };
+
+        let expected = quote::quote! {
+            trait Wheel {
+                #[doc(hidden)]
+                #[doc = "This is an internal function that is not meant to be used directly!"]
+                #[doc = "See the documentation of the `#[contract_trait]` attribute."]
+                fn __contracts_impl_digit(&self, base: u32) -> u32;
+
+                #[periodicity(base, +, 10)]
+                #[inline(always)]
+                fn digit(&self, base: u32) -> u32 {
+                    Self::__contracts_impl_digit(self, base.clone(),)
+                }
+
+                #[doc(hidden)]
+                #[doc = "This is an internal function that is not meant to be used directly!"]
+                #[doc = "See the documentation of the `#[contract_trait]` attribute."]
+                fn __contracts_impl_reset(&mut self, position: u32);
+
+                #[inline(always)]
+                fn reset(&mut self, position: u32) {
+                    Self::__contracts_impl_reset(self, position,)
+                }
+            }
+        };
+
+        let generated =
+            super::contract_trait_item_trait(Default::default(), code);
+
+        assert_eq!(generated.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn relations_clone_owned_generic_arguments() {
+        let code = syn::parse_quote! {
+            trait Scale {
+                #[monotonicity(factor, +, 1, <=)]
+                fn scale<T: ToString>(&self, factor: u32, unit: T) -> u32;
+            }
+        };
+
+        let expected = quote::quote! {
+            trait Scale {
+                #[doc(hidden)]
+                #[doc = "This is an internal function that is not meant to be used directly!"]
+                #[doc = "See the documentation of the `#[contract_trait]` attribute."]
+                fn __contracts_impl_scale<T: ToString>(&self, factor: u32, unit: T) -> u32;
+
+                #[monotonicity(factor, +, 1, <=)]
+                #[inline(always)]
+                fn scale<T: ToString + Clone>(&self, factor: u32, unit: T) -> u32 {
+                    Self::__contracts_impl_scale(self, factor.clone(), unit.clone(),)
+                }
+            }
+        };
+
+        let generated =
+            super::contract_trait_item_trait(Default::default(), code);
+
+        assert_eq!(generated.to_string(), expected.to_string());
+    }
+
     #[test]
     fn attributes_stay_on_trait_impl() {
         // attributes on functions should apply to the outer "wrapping" function
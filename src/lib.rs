@@ -157,6 +157,135 @@
 //!   }
 //!   ```
 //!
+//!   To compare against an alternative implementation, [`equivalent_to`]
+//!   takes care of calling it on copies of the inputs.
+//!
+//! ## Metamorphic relations
+//!
+//! The metamorphic relation attributes (`periodicity`, `monotonicity`,
+//! `homomorphism`, ...) execute the function body again on transformed
+//! inputs and check that the results of those follow-up runs relate to the
+//! result of the original call. Each follow-up run is also checked against all
+//! `requires`, `ensures` and `invariant` contracts.
+//!
+//! The transformed input can be a parameter or a field of it, like `book.id`
+//! or `self.angle`. For a field, the follow-up runs operate on a clone of the
+//! whole value in which only that field is transformed.
+//!
+//! Besides the positional arguments, the relations accept named arguments:
+//!
+//! - `output = expr` relates the post-states described by `expr` instead of
+//!   the return values, which is useful for methods that mutate `self` or a
+//!   `&mut` parameter.
+//! - `on = ret.0` or `on = |r| r.len()` relates only the selected part of the
+//!   return values (or of the `output` post-states), for example the result of
+//!   a function returning `(result, elapsed_ms)`.
+//! - `tolerance = 1e-9` compares floating point results up to the given
+//!   difference instead of exactly.
+//! - `when = expr` restricts the relation to source inputs for which `expr`
+//!   holds. The guard is evaluated before the call and may use `old()`; if it
+//!   is false, the follow-up runs of the relation are skipped entirely.
+//! - `transform = [rotate(1), reverse]` changes the input by several steps
+//!   in sequence and takes the place of the operator and the modifier, like
+//!   in `#[monotonicity(x, <, transform = [shift(1), scale(2)])]`. The steps
+//!   `shift(n)`, `scale(n)`, `reverse`, `rotate(n)` and the text
+//!   transformations are built in, any other step is a method call that
+//!   changes the input in place, like `swap(0, 1)`.
+//! - `compare = steps` runs the function after every step of the chain and
+//!   relates each output to the one of the step before, so
+//!   `f(x) < f(x + 1) < f(2 * (x + 1))` in the example above. It works with
+//!   the relations that have a single follow-up run, but not with `when`.
+//!
+//! Modifiers and named arguments may use `old()` just like `ensures` does.
+//! In a follow-up run, `old(expr)` refers to the state before that run, so
+//! `output = self.total - old(self.total)` relates the change of `self.total`
+//! made by each execution.
+//!
+//! Text parameters (`&str` and `String`) can be changed by the built-in text
+//! transformations, which are named instead of the operator:
+//!
+//! - `lowercase` and `uppercase` change the case of the text.
+//! - `trim` removes the surrounding whitespace, and `pad` adds it, a single
+//!   space or the given string like in `#[periodicity(host, pad("\t"))]`.
+//! - `percent_encode` encodes all bytes that are not unreserved in URLs.
+//! - `nfc` and `nfd` change the Unicode normalization form. They need the
+//!   `unicode_normalization` feature of this crate, and the crate using them
+//!   has to depend on `unicode-normalization`.
+//! - `insert` inserts a substring at a character index, like in
+//!   `#[monotonicity(word, insert, (2, "-"), <)]`.
+//!
+//! A transformation can be called with its modifier, like `pad("\t")`, which
+//! keeps a string modifier from being read as the description of the
+//! relation.
+//!
+//! These transformations are also the mirrors of a `symmetry` on text.
+//!
+//! A parameter passed by shared reference, like `&str`, `&[T]` or `&Book`, is
+//! transformed as an owned value (`String`, `Vec<T>` or `Book`, through
+//! `ToOwned`), and each follow-up run borrows its own owned input again.
+//! A function that returns a reference, like an accessor or a builder
+//! returning `&mut Self`, may return one into its own inputs: the result of
+//! each follow-up run is replaced by a reference to an owned snapshot before
+//! the next run starts, and the relation compares the referenced values.
+//!
+//! Parameters may be patterns like `(a, b): (i32, i32)` or
+//! `Point { x, y }: Point`. Contracts refer to the names they bind, and a
+//! relation transforms one of them, like `#[monotonicity(a, +, 1, <)]`. To
+//! transform the whole argument, name it with `@`, as in
+//! `p @ Point { x, y }: Point`: each follow-up run binds `x` and `y` again
+//! from its transformed `p`.
+//!
+//! Random number generators are not shared between the runs. The original
+//! call uses the generator of the caller as it is, and every follow-up run
+//! gets a new one for a `&mut` parameter whose type is named like a
+//! generator (`StdRng`, `ThreadRng`, `impl Rng`, or a generic bound by
+//! `RngCore` or `Rng`), or which is marked with `#[mr_seed]`:
+//!
+//! - a generator that is `Clone` is copied before the original call, so all
+//!   runs start from the same state. A generator that keeps its state
+//!   elsewhere still draws different numbers in every run: the clones of a
+//!   `ThreadRng` share the generator of their thread,
+//! - one that is only `rand::SeedableRng` is rebuilt from a fixed seed for
+//!   each follow-up run,
+//! - any other, like an `impl Rng`, is replaced by a `StdRng` from that seed.
+//!
+//! The last two need a dependency on `rand` with its `std_rng` feature, and
+//! only apply to generators named like the ones of `rand`. A generator that
+//! is only marked with `#[mr_seed]` has to be `Clone`, and needs no `rand`.
+//!
+//! ```rust
+//! # use contracts::*;
+//! use rand::{rngs::StdRng, Rng};
+//!
+//! #[periodicity(hour, +, 24)]
+//! fn arrival(rng: &mut StdRng, hour: u32) -> u32 {
+//!     (hour + rng.gen_range(0..3)) % 24
+//! }
+//! ```
+//!
+//! Like `invariant`, a relation can be put on an `impl` block to apply it to
+//! every method that has the transformed parameter. With `_` as the
+//! transformed variable and `param_type = "usize"`, the first parameter of
+//! that type is transformed instead, so a whole type can be enrolled at once.
+//! Methods that don't fit are skipped. [`mr_impl`] puts several relations on
+//! an `impl` block at once, like `#[mr_impl(deterministic, symmetry(x, -))]`.
+//!
+//! ```rust
+//! # use contracts::*;
+//! # use std::collections::HashSet;
+//! #[derive(Clone)]
+//! pub struct Library {
+//!     available: HashSet<usize>,
+//! }
+//!
+//! impl Library {
+//!     #[monotonicity(book_id, +, 1, <=, output = self.available.len())]
+//!     pub fn add_book(&mut self, book_id: usize) {
+//!         self.available.insert(book_id);
+//!     }
+//! }
+//! ```
+//!
 //! ## Feature flags
 //!
 //! Following feature flags are available:
@@ -174,6 +303,8 @@
 //! [precond]: attr.requires.html
 //! [postcond]: attr.ensures.html
 //! [invariant]: attr.invariant.html
+//! [`equivalent_to`]: attr.equivalent_to.html
+//! [`mr_impl`]: attr.mr_impl.html
 //! [MIRAI]: https://github.com/facebookexperimental/MIRAI
 
 extern crate proc_macro;
@@ -277,6 +408,305 @@ pub fn test_ensures(attr: TokenStream, toks: TokenStream) -> TokenStream {
     implementation::ensures(ContractMode::Test, attr, toks).into()
 }
 
+/// Check that a function behaves like a reference implementation, for
+/// example a slow but obviously correct one.
+///
+/// The reference implementation is called with the same arguments before the
+/// function body runs. Owned arguments and the values behind `&mut`
+/// parameters (including `&mut self`) are cloned for it, so it sees the
+/// inputs the body started with. Afterwards the return values and the
+/// mutated parameters are compared and a difference is reported with both
+/// values, which therefore have to implement `Debug`.
+///
+/// Values are compared with `==` unless one of these named arguments is
+/// given:
+///
+/// - `eq = same_elements` compares with a function or closure taking
+///   references to the value of the function and the value of the reference
+///   implementation. Closures with more than one parameter have to be put
+///   in parentheses.
+/// - `tolerance = 1e-9` compares floating point values up to the given
+///   difference.
+///
+/// Like the `test_*` contracts, the check is only enabled in `#[cfg(test)]`
+/// environments.
+///
+/// ## Example
+///
+/// ```rust
+/// # use contracts::*;
+/// mod reference {
+///     pub fn sort(input: &mut Vec<i32>) {
+///         for i in 0..input.len() {
+///             for j in 0..input.len() - i - 1 {
+///                 if input[j] > input[j + 1] {
+///                     input.swap(j, j + 1);
+///                 }
+///             }
+///         }
+///     }
+/// }
+///
+/// #[equivalent_to(reference::sort)]
+/// fn sort(input: &mut Vec<i32>) {
+///     input.sort_unstable();
+/// }
+/// # sort(&mut vec![3, 1, 2]);
+/// ```
+#[proc_macro_attribute]
+pub fn equivalent_to(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::equivalent_to(ContractMode::Test, attr, toks).into()
+}
+
+/// Check the methods of a type against a simpler model of its state.
+///
+/// The first argument is the type of the model, `abs = |value| ...` turns a
+/// value of the annotated type into its model. Before a method runs, the
+/// abstraction of `self` is taken and the method of the model with the same
+/// name is called on it, with the same arguments. Afterwards the return
+/// values, the mutated parameters and the abstraction of `self` have to equal
+/// those of the model, so they have to implement `PartialEq` and `Debug`.
+///
+/// Like invariants, the attribute is usually put on an `impl` block, where it
+/// applies to all methods taking `self`. Methods without a counterpart in
+/// the model can be excluded with `skip = [method, ...]`.
+///
+/// Like the `test_*` contracts, the check is only enabled in `#[cfg(test)]`
+/// environments.
+///
+/// ## Example
+///
+/// ```rust
+/// # use contracts::*;
+/// # use std::collections::HashSet;
+/// #[derive(Debug, PartialEq)]
+/// struct LibraryModel {
+///     available: HashSet<String>,
+///     lent: HashSet<String>,
+/// }
+///
+/// impl LibraryModel {
+///     fn add_book(&mut self, book_id: &str) {
+///         self.available.insert(book_id.to_string());
+///     }
+///
+///     fn lend(&mut self, book_id: &str) -> bool {
+///         let lent = self.available.remove(book_id);
+///         if lent {
+///             self.lent.insert(book_id.to_string());
+///         }
+///         lent
+///     }
+/// }
+///
+/// struct Library {
+///     books: Vec<(String, bool)>,
+/// }
+///
+/// #[model(
+///     LibraryModel,
+///     abs = |lib| LibraryModel {
+///         available: lib.ids(false),
+///         lent: lib.ids(true),
+///     },
+///     skip = [ids],
+/// )]
+/// impl Library {
+///     fn ids(&self, lent: bool) -> HashSet<String> {
+///         self.books
+///             .iter()
+///             .filter(|book| book.1 == lent)
+///             .map(|book| book.0.clone())
+///             .collect()
+///     }
+///
+///     fn add_book(&mut self, book_id: &str) {
+///         self.books.push((book_id.to_string(), false));
+///     }
+///
+///     fn lend(&mut self, book_id: &str) -> bool {
+///         match self.books.iter_mut().find(|book| book.0 == book_id) {
+///             Some(book) if !book.1 => {
+///                 book.1 = true;
+///                 true
+///             }
+///             _ => false,
+///         }
+///     }
+/// }
+/// # let mut lib = Library { books: vec![] };
+/// # lib.add_book("Das Kapital");
+/// # assert!(lib.lend("Das Kapital"));
+/// ```
+#[proc_macro_attribute]
+pub fn model(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::model(ContractMode::Test, attr, toks).into()
+}
+
+/// Check that a `&mut self` method commutes with another method.
+///
+/// The first argument is the name of the other method, the remaining ones are
+/// the arguments it is called with, which can use the parameters of the
+/// annotated method. Before the method runs, it is applied to one clone of
+/// `self` followed by the other method, and to another clone in the opposite
+/// order. The two resulting states have to be equal, so the type has to
+/// implement `Clone`, `PartialEq` and `Debug`.
+///
+/// `output = expr` compares the part of the states described by `expr`
+/// instead of the whole values. The arguments of the annotated method have to
+/// implement `Clone`.
+///
+/// The body of the annotated method is inlined for these runs, so its own
+/// contracts are not checked again. The other method is called normally, and
+/// if it commutes with the annotated method as well, its call back into the
+/// annotated method skips this check instead of starting it again.
+///
+/// Like the `test_*` contracts, the check is only enabled in `#[cfg(test)]`
+/// environments.
+///
+/// ## Example
+///
+/// ```rust
+/// # use contracts::*;
+/// # use std::collections::BTreeSet;
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Library {
+///     books: BTreeSet<usize>,
+/// }
+///
+/// impl Library {
+///     #[commutes_with(add_book, book_id + 1)]
+///     #[commutes_with(remove_book, book_id + 1)]
+///     fn add_book(&mut self, book_id: usize) {
+///         self.books.insert(book_id);
+///     }
+///
+///     fn remove_book(&mut self, book_id: usize) {
+///         self.books.remove(&book_id);
+///     }
+/// }
+/// # let mut lib = Library { books: BTreeSet::new() };
+/// # lib.add_book(1);
+/// ```
+#[proc_macro_attribute]
+pub fn commutes_with(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::commutes_with(ContractMode::Test, attr, toks).into()
+}
+
+/// Check that a `&self` method gives the same result when it is called from
+/// several threads at once.
+///
+/// Before the method runs, its body is run on `threads = n` threads (4 if not
+/// given) that share `self`, so the type has to be `Sync`. Every thread gets
+/// its own clones of the other arguments. Afterwards the result of each thread
+/// has to equal the result of the method, which therefore has to implement
+/// `Send`, `PartialEq` and `Debug`.
+///
+/// Like for [`equivalent_to`], `eq = relation` and `tolerance = 1e-9` change
+/// how the results are compared.
+///
+/// The body of the method is inlined for these runs, so its own contracts are
+/// not checked again. Like the `test_*` contracts, the check is only enabled
+/// in `#[cfg(test)]` environments.
+///
+/// ## Example
+///
+/// ```rust
+/// # use contracts::*;
+/// # use std::sync::Mutex;
+/// struct Cache {
+///     squares: Mutex<Vec<u64>>,
+/// }
+///
+/// impl Cache {
+///     #[concurrent_consistency(threads = 8)]
+///     fn square(&self, n: usize) -> u64 {
+///         let mut squares = self.squares.lock().unwrap();
+///         while squares.len() <= n {
+///             let next = squares.len() as u64;
+///             squares.push(next * next);
+///         }
+///         squares[n]
+///     }
+/// }
+/// # let cache = Cache { squares: Mutex::new(vec![]) };
+/// # cache.square(3);
+/// ```
+///
+/// [`equivalent_to`]: attr.equivalent_to.html
+#[proc_macro_attribute]
+pub fn concurrent_consistency(
+    attr: TokenStream,
+    toks: TokenStream,
+) -> TokenStream {
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::concurrent_consistency(ContractMode::Test, attr, toks)
+        .into()
+}
+
+/// Check a relation between the distributions of the results of a randomized
+/// function on an input and on a transformed input.
+///
+/// The first three arguments name the transformed parameter, the operator and
+/// the modifier, like for the other metamorphic relations. Before the function
+/// runs, its body is run `samples = n` times (1000 if not given) on the
+/// original and on the transformed input. The results have to be numbers,
+/// `on = expr` selects one from another return value like for the other
+/// relations. Owned parameters, `self` and the values behind `&mut`
+/// parameters are cloned for every run, so the caller's arguments are left
+/// as they are. A random number generator, recognized like for the other
+/// relations, is copied once, and the copy keeps producing new values from
+/// one run to the next, so the original and the transformed runs draw
+/// different numbers. A `&mut` parameter that is named like a generator but
+/// is not recognized as one, like `rng: &mut Lcg`, is an error, it has to be
+/// marked with `#[mr_seed]`.
+///
+/// `relation = name` selects the test of the two samples:
+///
+/// - `mean_eq` (the default) tests that the means are equal with Welch's
+///   t-test.
+/// - `dist_eq` tests that the distributions are equal with the two-sample
+///   Kolmogorov-Smirnov test.
+///
+/// The check fails if the p-value of the test is below `alpha = p` (0.01 if
+/// not given), so it fails falsely with that probability. Like the `test_*`
+/// contracts, the check is only enabled in `#[cfg(test)]` environments.
+///
+/// ## Example
+///
+/// ```rust
+/// # use contracts::*;
+/// #[derive(Clone)]
+/// struct Lcg(u64);
+///
+/// impl Lcg {
+///     fn next_f64(&mut self) -> f64 {
+///         self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1);
+///         (self.0 >> 11) as f64 / (1u64 << 53) as f64
+///     }
+/// }
+///
+/// // negating the input does not change the distribution of the results
+/// #[statistical(x, *, -1.0, samples = 500, relation = dist_eq)]
+/// fn noisy_abs(#[mr_seed] rng: &mut Lcg, x: f64) -> f64 {
+///     x.abs() + rng.next_f64() - 0.5
+/// }
+/// # noisy_abs(&mut Lcg(1), 1.0);
+/// ```
+#[proc_macro_attribute]
+pub fn statistical(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::statistical(ContractMode::Test, attr, toks).into()
+}
+
 /// Invariants are conditions that have to be maintained at the "interface
 /// boundaries".
 ///
@@ -418,6 +848,240 @@ pub fn mapping(attr: TokenStream, toks: TokenStream) -> TokenStream {
     implementation::mapping(mode, attr, toks).into()
 }
 
+/// Check that the result of a search or filter function grows or shrinks as
+/// a collection when its input is changed.
+///
+/// `#[inclusion(input, transform, Superset)]` runs the function again on a
+/// clone of `input` that is changed by `transform`, and checks that every
+/// element of the original result is contained in the result of that run.
+/// With `Subset`, every element of the follow-up result has to be contained
+/// in the original result instead. The results are compared with
+/// `iter().all(|e| other.contains(e))`, so they can be a `Vec`, a slice or a
+/// set.
+///
+/// A transformation starting with an operator, like `+ 1`, is applied to the
+/// input. Any other is a method call like `push(4)`, which changes the input
+/// in place. Like the other metamorphic relations, the follow-up run and the
+/// check are part of every build, not only of `#[cfg(test)]` ones.
+///
+/// ## Example
+///
+/// ```rust
+/// # use contracts::*;
+/// // adding an item can only add to the matches, raising the minimum can
+/// // only remove from them
+/// #[inclusion(items, push(9), Superset)]
+/// #[inclusion(min, + 1, Subset)]
+/// fn at_least(items: Vec<u32>, min: u32) -> Vec<u32> {
+///     items.iter().copied().filter(|item| *item >= min).collect()
+/// }
+/// # at_least(vec![1, 5, 3], 3);
+/// ```
+#[proc_macro_attribute]
+pub fn inclusion(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let mode = ContractMode::Test;
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::inclusion(mode, attr, toks).into()
+}
+
+/// Check that a function returns the same value when it runs again on the
+/// same inputs, which catches hidden dependencies on the iteration order of a
+/// `HashMap`, global counters or the time.
+///
+/// The second run gets a clone of the variable that is given as the first
+/// argument, or of the first parameter (`self` for methods) if there is none,
+/// and of all mutable parameters. A function without parameters is simply run
+/// again. With `mutated = true` the values behind `&mut self` and the `&mut`
+/// parameters are compared after both runs as well. Like the other
+/// metamorphic relations, the second run and the check are part of every
+/// build, not only of `#[cfg(test)]` ones.
+///
+/// ## Example
+///
+/// ```rust
+/// # use contracts::*;
+/// #[derive(Clone, PartialEq)]
+/// struct Counter {
+///     count: u32,
+/// }
+///
+/// impl Counter {
+///     #[deterministic(mutated = true)]
+///     fn bump(&mut self, by: u32) -> u32 {
+///         self.count += by;
+///         self.count
+///     }
+/// }
+/// # Counter { count: 0 }.bump(2);
+/// ```
+#[proc_macro_attribute]
+pub fn deterministic(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let mode = ContractMode::Test;
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::deterministic(mode, attr, toks).into()
+}
+
+/// Load metamorphic relations from a TOML file instead of writing them as
+/// attributes.
+///
+/// The path is relative to the `Cargo.toml` of the crate. The file maps the
+/// names of functions to arrays of relations, each of which has the same
+/// arguments as the attribute of the relation:
+///
+/// ```toml
+/// [[add_book]]
+/// relation = "monotonicity"
+/// input = "book_id"
+/// op = "+"
+/// modifier = 1
+/// result_op = "<="
+/// output = "self.available.len()"
+/// description = "adding books never shrinks the library"
+/// ```
+///
+/// The positional arguments are `input`, `op`, `modifier`, `result_op` and
+/// `result_modifier`, the named ones are `output`, `on`, `when`, `tolerance`
+/// and `transform`, which replaces `op` and `modifier`. Strings contain Rust
+/// code, numbers are used as they are.
+///
+/// The attribute can be put on functions or on `impl` blocks, where it
+/// applies to all methods that have relations in the file. Changing the file
+/// rebuilds the crate, and problems with it, like an unknown relation or key,
+/// are reported as errors at the path.
+///
+/// ```rust,ignore
+/// # use contracts::*;
+/// #[mr_file("relations/library.toml")]
+/// impl Library {
+///     pub fn add_book(&mut self, book_id: usize) {
+///         self.available.insert(book_id);
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn mr_file(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::mr_file(attr, toks).into()
+}
+
+/// Apply several metamorphic relations to the methods of an `impl` block.
+///
+/// Each relation is written like its attribute, the name alone or followed by
+/// its arguments, and is applied just like that attribute on the `impl` block
+/// would be: methods that don't have the transformed parameter are skipped.
+///
+/// ## Example
+///
+/// ```rust
+/// # use contracts::*;
+/// #[derive(Clone, PartialEq)]
+/// struct Shelf {
+///     books: Vec<usize>,
+/// }
+///
+/// #[mr_impl(deterministic, monotonicity(count, +, 1, <=))]
+/// impl Shelf {
+///     fn first(&self, count: usize) -> Vec<usize> {
+///         self.books.iter().copied().take(count).collect()
+///     }
+///
+///     fn len(&self) -> usize {
+///         self.books.len()
+///     }
+/// }
+/// # let shelf = Shelf { books: vec![1, 2] };
+/// # shelf.first(1);
+/// # shelf.len();
+/// ```
+#[proc_macro_attribute]
+pub fn mr_impl(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::mr_impl(attr, toks).into()
+}
+
+/// Define a suite of metamorphic relations which can be applied to a
+/// parameter with `#[mr_suite(name, parameter)]`.
+///
+/// The members are written like the relation attributes, but without the
+/// transformed parameter, which is filled in by `mr_suite`. Like other
+/// declarative macros, the suite has to be defined before it is used, and
+/// `#[mr_suite]` has to be placed before the other contract attributes of a
+/// function. Other modules of the crate name the suite by its path, like
+/// `#[mr_suite(crate::suites::linear, x)]`.
+///
+/// ```rust
+/// # use contracts::*;
+/// define_mr_suite!(linear, [
+///     homomorphism(+, 1),
+///     monotonicity(+, 1, <=),
+///     dimension_trans(*, 2, *),
+/// ]);
+///
+/// #[mr_suite(linear, x)]
+/// fn triple(x: i64) -> i64 {
+///     x * 3
+/// }
+/// # triple(2);
+/// ```
+#[proc_macro]
+pub fn define_mr_suite(toks: TokenStream) -> TokenStream {
+    let toks = toks.into();
+    implementation::define_mr_suite(toks).into()
+}
+
+/// Apply a suite of relations defined with `define_mr_suite!` to a parameter.
+///
+/// The suite is named by its name in the module where it is defined, or by
+/// its path anywhere else in the crate.
+#[proc_macro_attribute]
+pub fn mr_suite(attr: TokenStream, toks: TokenStream) -> TokenStream {
+    let attr = attr.into();
+    let toks = toks.into();
+    implementation::mr_suite(attr, toks).into()
+}
+
+/// Generate tests which check that method calls are independent of each
+/// other, so they return the same values in either order.
+///
+/// For every pair of the `independent` calls, a `#[test]` function creates two
+/// values with `setup`, makes both calls on the first value in one order and
+/// on the second value in the other order, and compares what each call
+/// returned. The return values therefore have to implement `PartialEq` and
+/// `Debug`.
+///
+/// - `setup: expr` creates the value the methods are called on. If it ends
+///   with `?`, it may fail like a `Result` or an `Option` does, and inputs for
+///   which it fails are skipped.
+/// - `inputs: p in [...]` runs the test for every input, which `setup` can
+///   refer to as `p`.
+/// - `independent: [method, other(args...)]` lists the calls.
+/// - `name: prefix` names the tests `prefix_method_0_other_1` instead of
+///   `mr_sequence_method_0_other_1`, where the numbers are the positions of
+///   the calls in `independent`.
+///
+/// Attributes written before the keys, like `#[ignore]`, are put on every
+/// generated test.
+///
+/// ```rust
+/// # use contracts::*;
+/// mr_sequence! {
+///     name: url_host,
+///     setup: url::Url::parse(p)?,
+///     inputs: p in ["https://example.com/a?b", "mailto:someone@example.com"],
+///     independent: [has_host, host_str, query],
+/// }
+/// # fn main() {}
+/// ```
+#[proc_macro]
+pub fn mr_sequence(toks: TokenStream) -> TokenStream {
+    let toks = toks.into();
+    implementation::mr_sequence(toks).into()
+}
+
 /// A "contract_trait" is a trait which ensures all implementors respect all
 /// provided contracts.
 ///
@@ -430,6 +1094,14 @@ pub fn mapping(attr: TokenStream, toks: TokenStream) -> TokenStream {
 /// **When the `#[contract_trait]` is not applied to either the trait or an
 /// `impl` it will cause compile errors**.
 ///
+/// Metamorphic relations on trait methods are checked for every implementor.
+/// Their follow-up runs call the implementation again with the transformed
+/// arguments, so arguments taken by value have to be `Clone`, and so does
+/// `Self` if the method takes `self` by value or as `&mut self`. Only these
+/// methods get a `where Self: Clone` bound (and `T: Clone` for generic
+/// arguments taken by value), the other methods of the trait and its
+/// implementors stay as they are.
+///
 /// ## Example
 ///
 /// ```rust
@@ -106,3 +106,137 @@ fn test_impl_trait_return() {
         r#""it worked" and "it worked""#
     );
 }
+
+mod reference {
+    pub fn sort(input: &mut [i32]) -> usize {
+        for i in 0..input.len() {
+            for j in 0..input.len() - i - 1 {
+                if input[j] > input[j + 1] {
+                    input.swap(j, j + 1);
+                }
+            }
+        }
+        input.len()
+    }
+
+    pub fn parity(x: i32) -> i32 {
+        x.rem_euclid(2)
+    }
+
+    pub fn mean(values: &[f64]) -> f64 {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+
+    pub fn clamp(bounds: (i32, i32), x: i32) -> i32 {
+        x.max(bounds.0).min(bounds.1)
+    }
+}
+
+#[test]
+fn test_equivalent_to() {
+    #[equivalent_to(reference::sort)]
+    fn sort(input: &mut Vec<i32>) -> usize {
+        input.sort_unstable();
+        input.len()
+    }
+
+    #[equivalent_to(reference::mean, tolerance = 1e-9)]
+    fn mean(values: &[f64]) -> f64 {
+        values.iter().rev().sum::<f64>() / values.len() as f64
+    }
+
+    #[equivalent_to(reference::parity, eq = (|a, b| a.abs() == b.abs()))]
+    fn parity(x: i32) -> i32 {
+        x % 2
+    }
+
+    let mut values = vec![3, 1, 2];
+    assert_eq!(sort(&mut values), 3);
+    assert_eq!(values, vec![1, 2, 3]);
+
+    mean(&[0.1, 0.2, 0.3]);
+    parity(-3);
+}
+
+#[test]
+#[should_panic(
+    expected = "equivalent_to of sort violated: `input` is [1, 2, 3] after \
+                `reference::sort`, but [3, 2, 1] after `sort`"
+)]
+fn test_equivalent_to_violation() {
+    #[equivalent_to(reference::sort)]
+    fn sort(input: &mut Vec<i32>) -> usize {
+        input.sort_unstable_by(|a, b| b.cmp(a));
+        input.len()
+    }
+
+    sort(&mut vec![3, 1, 2]);
+}
+
+#[test]
+#[should_panic(expected = "`sort_items` returned 3, but `count` returned 2")]
+fn test_equivalent_to_method_violation() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Items {
+        values: Vec<i32>,
+    }
+
+    fn sort_items(items: &mut Items) -> usize {
+        reference::sort(&mut items.values)
+    }
+
+    impl Items {
+        #[equivalent_to(sort_items)]
+        fn count(&mut self) -> usize {
+            self.values.sort_unstable();
+            self.values.len() - 1
+        }
+    }
+
+    Items {
+        values: vec![2, 3, 1],
+    }
+    .count();
+}
+
+#[test]
+fn test_pattern_parameters() {
+    #[derive(Clone)]
+    struct Span {
+        start: u32,
+        end: u32,
+    }
+
+    #[requires(start <= end, "the span must not be reversed")]
+    #[ensures(ret == end - old(start))]
+    fn len(Span { start, end }: Span) -> u32 {
+        end - start
+    }
+
+    #[requires(lo <= hi)]
+    #[ensures(lo <= ret && ret <= hi)]
+    #[equivalent_to(reference::clamp)]
+    fn clamp((lo, hi): (i32, i32), mut x: i32) -> i32 {
+        if x < lo {
+            x = lo;
+        }
+        if x > hi {
+            x = hi;
+        }
+        x
+    }
+
+    assert_eq!(len(Span { start: 2, end: 5 }), 3);
+    assert_eq!(clamp((0, 10), 12), 10);
+}
+
+#[test]
+#[should_panic(expected = "Pre-condition of len violated")]
+fn test_pattern_parameters_violation() {
+    #[requires(start <= end)]
+    fn len((start, end): (u32, u32)) -> u32 {
+        end.saturating_sub(start)
+    }
+
+    len((5, 2));
+}
@@ -0,0 +1,1193 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Testing of metamorphic relations and their follow-up executions.
+
+use contracts::*;
+
+#[test]
+fn test_follow_up_post_conditions() {
+    #[derive(Clone)]
+    struct Counter {
+        values: Vec<i32>,
+    }
+
+    impl Counter {
+        #[requires(x >= 0)]
+        #[invariant(self.values.len() < 10)]
+        #[ensures(ret >= old(x))]
+        #[ensures(self.values.len() == old(self.values.len()) + 1)]
+        #[monotonicity(x, +, 1, <=)]
+        fn push(&mut self, x: i32) -> i32 {
+            self.values.push(x);
+            x * 2
+        }
+    }
+
+    let mut counter = Counter { values: vec![] };
+    counter.push(3);
+    counter.push(4);
+
+    assert_eq!(counter.values, vec![3, 4]);
+}
+
+#[test]
+#[should_panic(expected = "Post-condition of wrap violated for extra run")]
+fn test_follow_up_post_condition_violation() {
+    #[ensures(ret < 10)]
+    #[periodicity(x, +, 4)]
+    fn wrap(x: i32) -> i32 {
+        x
+    }
+
+    // the source execution is fine, the follow-up with `x + 4` is not
+    wrap(7);
+}
+
+#[test]
+fn test_output_projection() {
+    use std::collections::HashSet;
+
+    #[derive(Clone)]
+    struct Library {
+        available: HashSet<usize>,
+    }
+
+    impl Library {
+        #[monotonicity(book_id, +, 1, <=, output = self.available.len())]
+        #[periodicity(book_id, +, 0, output = self.available.len())]
+        fn add_book(&mut self, book_id: usize) {
+            self.available.insert(book_id);
+        }
+    }
+
+    let mut lib = Library {
+        available: HashSet::new(),
+    };
+    lib.add_book(1);
+    lib.add_book(2);
+
+    assert_eq!(lib.available.len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of incr violated")]
+fn test_output_projection_violation() {
+    #[periodicity(amount, +, 1, output = *total)]
+    fn incr(total: &mut usize, amount: usize) {
+        *total += amount;
+    }
+
+    let mut total = 0;
+    incr(&mut total, 1);
+}
+
+#[test]
+fn test_on_selector() {
+    #[periodicity(x, +, 4, on = ret.0)]
+    fn timed(x: u32) -> (u32, u128) {
+        (x % 4, u128::from(x) * 7)
+    }
+
+    #[monotonicity(len, +, 1, <=, on = |r| r.len())]
+    fn upto(len: usize) -> Vec<usize> {
+        (0..len).collect()
+    }
+
+    assert_eq!(timed(5), (1, 35));
+    assert_eq!(upto(3), vec![0, 1, 2]);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of timed violated")]
+fn test_on_selector_violation() {
+    #[periodicity(x, +, 4, on = ret.1)]
+    fn timed(x: u32) -> (u32, u128) {
+        (x % 4, u128::from(x) * 7)
+    }
+
+    timed(5);
+}
+
+#[test]
+fn test_field_transformation() {
+    #[derive(Clone)]
+    struct Book {
+        id: usize,
+        author: String,
+    }
+
+    #[derive(Clone)]
+    struct Dial {
+        angle: i32,
+    }
+
+    impl Dial {
+        #[periodicity(self.angle, +, 360)]
+        fn quadrant(&self) -> i32 {
+            self.angle.rem_euclid(360) / 90
+        }
+    }
+
+    #[monotonicity(book.id, +, 1, <, on = ret.0)]
+    fn shelf(book: Book) -> (usize, String) {
+        (book.id + 1, book.author)
+    }
+
+    assert_eq!(Dial { angle: 100 }.quadrant(), 1);
+    let book = Book {
+        id: 3,
+        author: String::from("Marx"),
+    };
+    assert_eq!(shelf(book), (4, String::from("Marx")));
+}
+
+#[test]
+#[should_panic(expected = "periodicity of quadrant violated")]
+fn test_field_transformation_violation() {
+    #[derive(Clone)]
+    struct Dial {
+        angle: i32,
+    }
+
+    impl Dial {
+        #[periodicity(self.angle, +, 180)]
+        fn quadrant(&self) -> i32 {
+            self.angle.rem_euclid(360) / 90
+        }
+    }
+
+    Dial { angle: 100 }.quadrant();
+}
+
+#[test]
+fn test_when_guard() {
+    #[monotonicity(x, +, 1, <=, when = x >= 0)]
+    fn abs(x: i32) -> i32 {
+        x.abs()
+    }
+
+    #[homomorphism(x, +, 1, when = old(x) > 0)]
+    fn double(x: u32) -> Option<u32> {
+        Some(x * 2)
+    }
+
+    // outside of the guarded domain the relation does not hold
+    assert_eq!(abs(-3), 3);
+    assert_eq!(abs(3), 3);
+    assert_eq!(double(4), Some(8));
+}
+
+#[test]
+#[should_panic(expected = "monotonicity of abs violated")]
+fn test_when_guard_violation() {
+    #[monotonicity(x, +, 1, <=, when = x < 0)]
+    fn abs(x: i32) -> i32 {
+        x.abs()
+    }
+
+    abs(-3);
+}
+
+#[test]
+fn test_old_in_relations() {
+    #[derive(Clone)]
+    struct Counter {
+        step: u32,
+        total: u32,
+    }
+
+    impl Counter {
+        #[monotonicity(x, +, old(self.step), <=, output = self.total - old(self.total))]
+        fn add(&mut self, x: u32) {
+            self.total += x;
+        }
+    }
+
+    let mut counter = Counter { step: 2, total: 5 };
+    counter.add(3);
+    counter.add(1);
+
+    assert_eq!(counter.total, 9);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of add violated")]
+fn test_old_in_relations_violation() {
+    #[derive(Clone)]
+    struct Counter {
+        total: u32,
+    }
+
+    impl Counter {
+        #[periodicity(x, +, 1, output = self.total - old(self.total))]
+        fn add(&mut self, x: u32) {
+            self.total += x;
+        }
+    }
+
+    Counter { total: 5 }.add(3);
+}
+
+#[test]
+fn test_relation_on_impl() {
+    #[derive(Clone)]
+    struct Shelf {
+        books: Vec<usize>,
+    }
+
+    #[monotonicity(count, +, 1, <=)]
+    #[monotonicity(_, +, 1, <=, param_type = "usize")]
+    impl Shelf {
+        fn first(&self, count: usize) -> Vec<usize> {
+            self.books.iter().copied().take(count).collect::<Vec<_>>()
+        }
+
+        fn above(&self, bound: usize) -> usize {
+            self.books.iter().filter(|b| **b < bound).count()
+        }
+
+        // does not fit either relation and is left alone
+        fn clear(&mut self) {
+            self.books.clear();
+        }
+    }
+
+    let mut shelf = Shelf {
+        books: vec![1, 2, 3],
+    };
+    assert_eq!(shelf.first(2), vec![1, 2]);
+    assert_eq!(shelf.above(3), 2);
+    shelf.clear();
+}
+
+#[test]
+fn test_relations_of_mr_impl() {
+    #[derive(Clone, PartialEq)]
+    struct Shelf {
+        books: Vec<usize>,
+    }
+
+    #[mr_impl(deterministic, monotonicity(count, +, 1, <=))]
+    impl Shelf {
+        fn first(&self, count: usize) -> Vec<usize> {
+            self.books.iter().copied().take(count).collect::<Vec<_>>()
+        }
+
+        fn len(&self) -> usize {
+            self.books.len()
+        }
+    }
+
+    let shelf = Shelf {
+        books: vec![1, 2, 3],
+    };
+    assert_eq!(shelf.first(2), vec![1, 2]);
+    assert_eq!(shelf.len(), 3);
+}
+
+#[test]
+#[should_panic(expected = "monotonicity of above violated")]
+fn test_relations_of_mr_impl_violation() {
+    #[derive(Clone, PartialEq)]
+    struct Shelf {
+        books: Vec<usize>,
+    }
+
+    #[mr_impl(deterministic, monotonicity(min, +, 1, <=))]
+    impl Shelf {
+        fn above(&self, min: usize) -> usize {
+            self.books.iter().filter(|b| **b > min).count()
+        }
+    }
+
+    Shelf {
+        books: vec![1, 2, 3],
+    }
+    .above(1);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of mirror violated")]
+fn test_relation_on_impl_violation() {
+    #[derive(Clone)]
+    struct Dial {
+        angle: i32,
+    }
+
+    #[periodicity(self.angle, +, 360)]
+    impl Dial {
+        fn quadrant(&self) -> i32 {
+            self.angle.rem_euclid(360) / 90
+        }
+
+        fn mirror(&self) -> i32 {
+            -self.angle
+        }
+
+        fn straight() -> i32 {
+            180
+        }
+    }
+
+    assert_eq!(Dial::straight(), 180);
+    assert_eq!(Dial { angle: 100 }.quadrant(), 1);
+    Dial { angle: 100 }.mirror();
+}
+
+#[test]
+fn test_relation_file() {
+    use std::collections::HashSet;
+
+    #[derive(Clone)]
+    struct Library {
+        available: HashSet<usize>,
+    }
+
+    #[mr_file("tests/relations/metamorphic.toml")]
+    impl Library {
+        fn add_book(&mut self, book_id: usize) {
+            self.available.insert(book_id);
+        }
+
+        fn is_empty(&self) -> bool {
+            self.available.is_empty()
+        }
+    }
+
+    #[mr_file("tests/relations/metamorphic.toml")]
+    fn tenth(x: f64) -> f64 {
+        x * 0.1
+    }
+
+    let mut lib = Library {
+        available: HashSet::new(),
+    };
+    lib.add_book(1);
+
+    assert!(!lib.is_empty());
+    assert!((tenth(0.7) - 0.07).abs() < 1e-9);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of wrap violated")]
+fn test_relation_file_violation() {
+    #[mr_file("tests/relations/metamorphic.toml")]
+    #[ensures(ret < 5)]
+    fn wrap(x: i32) -> i32 {
+        x % 4
+    }
+
+    wrap(3);
+}
+
+define_mr_suite!(linear, [
+    homomorphism(+, 1),
+    monotonicity(+, 1, <=),
+    dimension_trans(*, 2, *),
+]);
+
+define_mr_suite!(growing, [monotonicity(+, 1, <=, output = self.total)]);
+
+define_mr_suite!(ignored, [periodicity(+, 1), periodicity(*, 2)]);
+
+#[test]
+fn test_relation_suite() {
+    #[derive(Clone)]
+    struct Counter {
+        total: u64,
+    }
+
+    impl Counter {
+        #[mr_suite(growing, x)]
+        fn add(&mut self, x: u64) {
+            self.total += x;
+        }
+    }
+
+    #[mr_suite(linear, x)]
+    #[mr_suite(ignored, y)]
+    #[requires(x >= 0)]
+    fn scale(x: i64, y: i64) -> i64 {
+        x * 3 + y * 0
+    }
+
+    let mut counter = Counter { total: 0 };
+    counter.add(2);
+
+    assert_eq!(counter.total, 2);
+    assert_eq!(scale(2, 1), 6);
+}
+
+#[test]
+#[should_panic(expected = "homomorphism of offset violated")]
+fn test_relation_suite_violation() {
+    #[mr_suite(linear, x)]
+    fn offset(x: i64) -> i64 {
+        x + 1
+    }
+
+    offset(2);
+}
+
+mod suites {
+    use contracts::*;
+
+    define_mr_suite!(doubling, [dimension_trans(*, 2, *)]);
+}
+
+mod geometry {
+    use contracts::*;
+
+    #[mr_suite(crate::suites::doubling, side)]
+    pub fn perimeter(side: u32) -> u32 {
+        side * 4
+    }
+
+    #[mr_suite(super::linear, x)]
+    pub fn offset(x: i64) -> i64 {
+        x + 1
+    }
+}
+
+#[test]
+fn test_relation_suite_from_other_module() {
+    assert_eq!(geometry::perimeter(3), 12);
+}
+
+#[test]
+#[should_panic(expected = "homomorphism of offset violated")]
+fn test_relation_suite_from_other_module_violation() {
+    geometry::offset(2);
+}
+
+#[test]
+fn test_deterministic() {
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, PartialEq)]
+    struct Counter {
+        count: u32,
+    }
+
+    #[deterministic]
+    impl Counter {
+        fn get(&self) -> u32 {
+            self.count
+        }
+
+        #[deterministic(by, mutated = true)]
+        fn bump(&mut self, by: u32) -> u32 {
+            self.count += by;
+            self.count
+        }
+    }
+
+    #[deterministic]
+    fn frequencies(words: &[&str]) -> Vec<(String, usize)> {
+        let mut counts = BTreeMap::new();
+        for word in words {
+            *counts.entry(word.to_string()).or_insert(0) += 1;
+        }
+        counts.into_iter().collect()
+    }
+
+    #[deterministic]
+    fn answer() -> u32 {
+        42
+    }
+
+    let mut counter = Counter { count: 0 };
+    counter.bump(2);
+
+    assert_eq!(counter.get(), 2);
+    assert_eq!(answer(), 42);
+    assert_eq!(
+        frequencies(&["b", "a", "b"]),
+        vec![(String::from("a"), 1), (String::from("b"), 2)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "deterministic of next_id violated")]
+fn test_deterministic_violation() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    #[deterministic]
+    fn next_id(prefix: &str) -> String {
+        format!("{}{}", prefix, NEXT.fetch_add(1, Ordering::SeqCst))
+    }
+
+    next_id("id-");
+}
+
+#[test]
+#[should_panic(expected = "deterministic of next_ticket violated")]
+fn test_deterministic_without_parameters() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    #[deterministic]
+    fn next_ticket() -> usize {
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    }
+
+    next_ticket();
+}
+
+#[test]
+#[should_panic(expected = "self after f(x) = self after f(x)")]
+fn test_deterministic_mutated_violation() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Clone, PartialEq)]
+    struct Log {
+        entries: Vec<(usize, String)>,
+    }
+
+    impl Log {
+        #[deterministic(mutated = true)]
+        fn push(&mut self, entry: &str) -> usize {
+            let call = CALLS.fetch_add(1, Ordering::SeqCst);
+            self.entries.push((call, entry.to_string()));
+            self.entries.len()
+        }
+    }
+
+    let mut log = Log { entries: vec![] };
+    log.push("start");
+}
+
+/// A linear congruential generator, enough to draw samples in the tests.
+#[derive(Clone, Debug, PartialEq)]
+struct Lcg(u64);
+
+impl Lcg {
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[test]
+fn test_statistical() {
+    #[derive(Clone)]
+    struct Dice {
+        sides: u32,
+        rolls: u32,
+    }
+
+    impl Dice {
+        #[statistical(bonus, +, 0, samples = 200, on = ret.0)]
+        fn roll(
+            &mut self,
+            #[mr_seed] rng: &mut Lcg,
+            bonus: u32,
+        ) -> (u32, u32) {
+            self.rolls += 1;
+            let roll = (rng.next_f64() * self.sides as f64) as u32 + 1;
+            (roll + bonus, self.rolls)
+        }
+    }
+
+    #[statistical(x, -, 4.0, relation = dist_eq, alpha = 0.001)]
+    fn noisy_wrap(#[mr_seed] rng: &mut Lcg, x: f64) -> f64 {
+        x.rem_euclid(4.0) + rng.next_f64() - 0.5
+    }
+
+    // the samples run on copies, the caller only sees the actual call
+    #[statistical(x, +, 0)]
+    fn drain(history: &mut Vec<u32>, x: u32) -> u32 {
+        history.push(x);
+        history.len() as u32
+    }
+
+    let mut rng = Lcg(7);
+    let mut expected = Lcg(7);
+    let mut dice = Dice { sides: 6, rolls: 0 };
+
+    assert_eq!(dice.roll(&mut rng, 1).1, 1);
+    expected.next_f64();
+    assert_eq!(rng, expected);
+
+    assert!((noisy_wrap(&mut rng, 6.0) - 2.0).abs() <= 0.5);
+
+    let mut history = vec![];
+    assert_eq!(drain(&mut history, 3), 1);
+    assert_eq!(history, vec![3]);
+}
+
+#[test]
+fn test_statistical_streams() {
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+
+    thread_local! {
+        static DRAWN: RefCell<Vec<u64>> = const { RefCell::new(vec![]) };
+    }
+
+    #[derive(Clone)]
+    struct Recorder(Lcg);
+
+    impl Recorder {
+        fn next_f64(&mut self) -> f64 {
+            let value = self.0.next_f64();
+            DRAWN.with(|drawn| drawn.borrow_mut().push(value.to_bits()));
+            value
+        }
+    }
+
+    #[statistical(x, +, 0.0, samples = 100)]
+    fn draw(#[mr_seed] rng: &mut Recorder, x: f64) -> f64 {
+        x + rng.next_f64()
+    }
+
+    draw(&mut Recorder(Lcg(7)), 0.0);
+
+    // the original and the transformed samples are drawn one after another,
+    // before the actual call
+    let drawn = DRAWN.with(|drawn| drawn.borrow().clone());
+    assert_eq!(drawn.len(), 201);
+    assert_eq!(drawn[..200].iter().collect::<HashSet<_>>().len(), 200);
+}
+
+#[test]
+#[should_panic(expected = "statistical of shift violated: `mean_eq` of 1000 \
+                           samples of `shift` with `x` and `x + 1.0`")]
+fn test_statistical_violation() {
+    #[statistical(x, +, 1.0)]
+    fn shift(#[mr_seed] rng: &mut Lcg, x: f64) -> f64 {
+        x + rng.next_f64()
+    }
+
+    shift(&mut Lcg(7), 0.0);
+}
+
+#[test]
+fn test_inclusion() {
+    use std::collections::BTreeSet;
+
+    #[inclusion(items, push(9), Superset)]
+    #[inclusion(min, + 1, Subset)]
+    fn at_least(items: Vec<u32>, min: u32) -> Vec<u32> {
+        items.iter().copied().filter(|item| *item >= min).collect()
+    }
+
+    #[derive(Clone)]
+    struct Index {
+        words: Vec<String>,
+    }
+
+    impl Index {
+        #[inclusion(query, push('s'), Subset)]
+        fn search(&self, mut query: String) -> Option<BTreeSet<&str>> {
+            query.make_ascii_lowercase();
+            let hits = self
+                .words
+                .iter()
+                .filter(|word| word.contains(query.as_str()))
+                .map(String::as_str);
+            Some(hits.collect())
+        }
+    }
+
+    let index = Index {
+        words: vec![String::from("rusts"), String::from("trust")],
+    };
+
+    assert_eq!(at_least(vec![1, 5, 3], 3), vec![5, 3]);
+    assert_eq!(index.search(String::from("RUST")).unwrap().len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "inclusion of top violated")]
+fn test_inclusion_violation() {
+    // the largest items change when one is added
+    #[inclusion(items, push(9), Superset)]
+    fn top(mut items: Vec<u32>) -> Vec<u32> {
+        items.sort_unstable();
+        items.split_off(items.len() - 2)
+    }
+
+    top(vec![1, 5, 3]);
+}
+
+#[test]
+fn test_text_transformations() {
+    #[periodicity(host, lowercase)]
+    #[periodicity(host, pad)]
+    #[periodicity(host, pad("\t\n"))]
+    fn normalize_host(host: &str) -> String {
+        host.trim().to_ascii_lowercase()
+    }
+
+    #[periodicity(value, percent_encode)]
+    fn query_value(value: &str) -> String {
+        let url = String::from("http://example.com/?q=") + value;
+        let parsed = url::Url::parse(&url).unwrap();
+        let (_, value) = parsed.query_pairs().next().unwrap();
+        value.into_owned()
+    }
+
+    #[monotonicity(word, insert, (2, "-"), <)]
+    #[monotonicity(word, insert(0, "-"), , <)]
+    #[symmetry(word, uppercase, , +)]
+    fn length(word: &str) -> usize {
+        word.len()
+    }
+
+    assert_eq!(normalize_host(" Example.COM "), "example.com");
+    assert_eq!(query_value("café crème"), "café crème");
+    assert_eq!(length("abc"), 3);
+}
+
+#[test]
+#[cfg(feature = "unicode_normalization")]
+fn test_unicode_normalization() {
+    use unicode_normalization::UnicodeNormalization;
+
+    #[periodicity(word, nfd)]
+    #[periodicity(word, nfc)]
+    fn letters(word: String) -> usize {
+        word.nfc().count()
+    }
+
+    assert_eq!(letters(String::from("cafe\u{301}")), 4);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of normalize_host violated")]
+fn test_text_padding_violation() {
+    // only spaces are removed, so padding with tabs changes the result
+    #[periodicity(host, pad("\t"))]
+    fn normalize_host(host: &str) -> String {
+        host.trim_matches(' ').to_ascii_lowercase()
+    }
+
+    normalize_host("example.com");
+}
+
+#[test]
+#[should_panic(expected = "periodicity of query_value violated")]
+fn test_text_transformation_violation() {
+    // `&` ends the value unless it is encoded
+    #[periodicity(value, percent_encode)]
+    fn query_value(value: &str) -> String {
+        let url = String::from("http://example.com/?q=") + value;
+        let parsed = url::Url::parse(&url).unwrap();
+        let (_, value) = parsed.query_pairs().next().unwrap();
+        value.into_owned()
+    }
+
+    query_value("fish&chips");
+}
+
+#[test]
+fn test_transform_chain() {
+    #[periodicity(xs, transform = [rotate(1), reverse, swap(0, 1)])]
+    #[inclusion(xs, Superset, transform = [push(9), reverse])]
+    fn positive(xs: Vec<i32>) -> Vec<i32> {
+        let mut positive: Vec<i32> =
+            xs.iter().copied().filter(|x| *x > 0).collect();
+        positive.sort_unstable();
+        positive
+    }
+
+    #[monotonicity(x, <, transform = [shift(1), scale(2)])]
+    fn double(x: u32) -> u32 {
+        2 * x
+    }
+
+    #[periodicity(host, transform = [uppercase, pad, reverse, reverse])]
+    fn normalize_host(host: &str) -> String {
+        host.trim().to_ascii_lowercase()
+    }
+
+    assert_eq!(positive(vec![3, -1, 2]), vec![2, 3]);
+    assert_eq!(double(3), 6);
+    assert_eq!(normalize_host("example.com"), "example.com");
+}
+
+#[test]
+#[should_panic(expected = "periodicity of first violated")]
+fn test_transform_chain_violation() {
+    // the rotation moves the pushed element to the front
+    #[periodicity(xs, transform = [push(1), rotate(1)])]
+    fn first(xs: Vec<i32>) -> i32 {
+        xs[0]
+    }
+
+    first(vec![5, 6]);
+}
+
+#[test]
+fn test_transform_chain_steps() {
+    #[monotonicity(x, <, transform = [shift(1), scale(2)], compare = steps)]
+    fn double(x: u32) -> u32 {
+        2 * x
+    }
+
+    #[periodicity(x, transform = [shift(3), shift(6)], compare = steps)]
+    fn residue(x: i32) -> Option<i32> {
+        Some(x.rem_euclid(3))
+    }
+
+    #[periodicity(
+        xs,
+        transform = [reverse, rotate(1)],
+        compare = steps,
+        on = ret.0
+    )]
+    fn stats(xs: Vec<i32>) -> (i32, Option<i32>) {
+        (xs.iter().sum(), xs.first().copied())
+    }
+
+    #[derive(Clone)]
+    struct Tally {
+        count: usize,
+    }
+
+    impl Tally {
+        #[monotonicity(
+            extra,
+            <=,
+            transform = [shift(1), shift(2)],
+            compare = steps,
+            output = self.count
+        )]
+        fn add(&mut self, extra: usize) {
+            self.count += extra;
+        }
+    }
+
+    let mut tally = Tally { count: 0 };
+    tally.add(2);
+
+    assert_eq!(double(3), 6);
+    assert_eq!(residue(4), Some(1));
+    assert_eq!(stats(vec![1, 2, 3]), (6, Some(1)));
+    assert_eq!(tally.count, 2);
+}
+
+#[test]
+#[should_panic(expected = "monotonicity of identity violated")]
+fn test_transform_chain_steps_violation() {
+    // the input grows from the original one, but not from the step before
+    #[monotonicity(x, <=, transform = [shift(10), shift(-5)], compare = steps)]
+    fn identity(x: i32) -> i32 {
+        x
+    }
+
+    identity(1);
+}
+
+#[test]
+fn test_seeded_generator() {
+    use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+    type Source = StdRng;
+
+    // every run draws the same noise, so only the shift of `x` is seen
+    #[periodicity(x, +, 4)]
+    fn jitter(rng: &mut StdRng, x: u32) -> u32 {
+        x % 4 + rng.gen_range(0..100) * 4
+    }
+
+    #[periodicity(items, +, 3)]
+    fn sample<G>(rng: &mut G, items: u32) -> u32
+    where
+        G: RngCore + SeedableRng + Clone,
+    {
+        items % 3 + rng.next_u32() % 8 * 3
+    }
+
+    #[deterministic]
+    #[periodicity(x, +, 2)]
+    fn coin(#[mr_seed] source: &mut Source, x: u8) -> bool {
+        source.gen_bool(0.5) ^ (x % 2 == 1)
+    }
+
+    let seeded = || StdRng::seed_from_u64(1);
+
+    assert_eq!(jitter(&mut seeded(), 2), jitter(&mut seeded(), 6));
+    assert_eq!(sample(&mut seeded(), 1), sample(&mut seeded(), 4));
+    assert_eq!(coin(&mut seeded(), 1), coin(&mut seeded(), 3));
+
+    // the original call draws from the generator of the caller, the
+    // follow-up run from its own copy
+    let mut rng = seeded();
+    let mut expected = seeded();
+    assert_eq!(jitter(&mut rng, 2), 2 + expected.gen_range(0..100) * 4);
+    assert_eq!(rng.next_u64(), expected.next_u64());
+}
+
+#[test]
+fn test_unseeded_generator() {
+    use rand::{rngs::ThreadRng, Rng, RngCore, SeedableRng};
+
+    // the follow-up runs draw other numbers than the original call, which
+    // the relations allow for
+    #[monotonicity(x, +, 10, <)]
+    fn noisy(rng: &mut ThreadRng, x: u32) -> u32 {
+        x + rng.gen_range(0..10)
+    }
+
+    #[monotonicity(x, +, 10, <)]
+    fn shaky(rng: &mut impl Rng, x: u32) -> u32 {
+        x + rng.gen_range(0..10)
+    }
+
+    #[monotonicity(x, +, 10, <)]
+    fn reseeded<G: RngCore + SeedableRng>(rng: &mut G, x: u32) -> u32 {
+        x + rng.next_u32() % 10
+    }
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let mut expected = rand::rngs::StdRng::seed_from_u64(1);
+
+    assert!(noisy(&mut rand::thread_rng(), 3) < 13);
+    assert_eq!(shaky(&mut rng, 3), 3 + expected.gen_range(0..10));
+    assert_eq!(reseeded(&mut rng, 3), 3 + expected.next_u32() % 10);
+    assert_eq!(rng.next_u64(), expected.next_u64());
+}
+
+#[test]
+#[should_panic(expected = "periodicity of jitter violated")]
+fn test_seeded_generator_violation() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[periodicity(x, +, 4)]
+    fn jitter(rng: &mut StdRng, x: u32) -> u32 {
+        x + rng.gen_range(0..100)
+    }
+
+    jitter(&mut StdRng::seed_from_u64(1), 2);
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+struct Book {
+    id: u32,
+    author: String,
+}
+
+impl Book {
+    fn add(&mut self, n: u32) -> Book {
+        self.id += n;
+        self.clone()
+    }
+
+    fn ref_add(&mut self, n: u32) -> &Book {
+        self.id += n;
+        self
+    }
+
+    #[monotonicity(n, +, 1, <)]
+    #[ensures(ret.id >= n)]
+    fn shifted(&mut self, n: u32) -> &Book {
+        self.id += n;
+        self
+    }
+
+    #[periodicity(name, uppercase)]
+    fn with_author(&mut self, name: &str) -> &mut Book {
+        self.author = name.len().to_string();
+        self
+    }
+
+    #[periodicity(self, ref_add, 0)]
+    fn author(&self) -> &str {
+        &self.author
+    }
+
+    // the follow-up run builds a book with another id
+    #[periodicity(n, +, 1)]
+    fn with_id(&mut self, n: u32) -> &mut Book {
+        self.id = n;
+        self
+    }
+}
+
+#[test]
+fn test_borrowed_parameters() {
+    #[monotonicity(word, +, "s", <)]
+    #[periodicity(word, uppercase, when = word.is_ascii())]
+    fn letters(word: &str) -> usize {
+        word.len()
+    }
+
+    #[periodicity(xs, transform = [reverse])]
+    #[inclusion(xs, push(9), Superset)]
+    fn sorted(xs: &[i32]) -> Vec<i32> {
+        let mut sorted = xs.to_vec();
+        sorted.sort_unstable();
+        sorted
+    }
+
+    #[monotonicity(book, add, 1, <)]
+    #[monotonicity(book, ref_add, 1, <)]
+    #[monotonicity(book.id, +, 2, <)]
+    fn shelf(book: &Book) -> u32 {
+        book.id * 10
+    }
+
+    assert_eq!(letters("ab"), 2);
+    assert_eq!(sorted(&[3, 1, 2]), vec![1, 2, 3]);
+    assert_eq!(shelf(&Book { id: 1, author: String::from("Marx") }), 10);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of first violated")]
+fn test_borrowed_parameter_violation() {
+    #[periodicity(xs, transform = [reverse])]
+    fn first(xs: &[i32]) -> i32 {
+        xs[0]
+    }
+
+    first(&[1, 2]);
+}
+
+#[test]
+fn test_borrowed_results() {
+    #[monotonicity(x, +, 1, <=)]
+    fn largest(xs: &mut Vec<u32>, x: u32) -> &u32 {
+        xs.push(x);
+        xs.iter().max().unwrap()
+    }
+
+    let mut book = Book {
+        id: 1,
+        author: String::from("Marx"),
+    };
+
+    assert_eq!(book.shifted(2).id, 3);
+    assert_eq!(book.with_author("Marx").author, "4");
+    assert_eq!(book.author(), "4");
+    assert_eq!(largest(&mut vec![4, 1], 2), &4);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of with_id violated")]
+fn test_borrowed_result_violation() {
+    Book {
+        id: 1,
+        author: String::from("Marx"),
+    }
+    .with_id(2);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+impl Point {
+    fn mirrored(&self) -> Point {
+        Point {
+            x: self.y,
+            y: self.x,
+        }
+    }
+}
+
+#[test]
+fn test_pattern_parameters() {
+    #[monotonicity(a, +, 1, <)]
+    #[periodicity(b, +, 0)]
+    #[deterministic]
+    fn sum((mut a, b): (i32, i32)) -> i32 {
+        a += b;
+        a
+    }
+
+    #[monotonicity(x, +, 1, <=, when = x >= 0)]
+    fn distance(Point { x, y }: Point) -> i32 {
+        x.abs() + y.abs()
+    }
+
+    // the parts are bound again from the mirrored point, so `p.x - x` stays
+    // zero
+    #[periodicity(p, mirrored)]
+    #[periodicity(x, +, 0)]
+    fn manhattan(p @ Point { x, y }: Point) -> i32 {
+        (p.x - x) + x.abs() + y.abs()
+    }
+
+    #[monotonicity(word, +, "s", <)]
+    #[monotonicity(n, +, 1, <=)]
+    fn longest(
+        (ref word, ref mut seen): (String, Vec<usize>),
+        n: usize,
+    ) -> usize {
+        seen.push(word.len() + n);
+        *seen.iter().max().unwrap()
+    }
+
+    assert_eq!(sum((1, 2)), 3);
+    assert_eq!(distance(Point { x: 1, y: -2 }), 3);
+    assert_eq!(manhattan(Point { x: 1, y: -2 }), 3);
+    assert_eq!(longest((String::from("ab"), vec![1]), 0), 2);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of first violated")]
+fn test_pattern_parameter_violation() {
+    #[periodicity(p, mirrored)]
+    fn first(p @ Point { x, .. }: Point) -> (i32, i32) {
+        (p.x, x)
+    }
+
+    first(Point { x: 1, y: 2 });
+}
+
+mr_sequence! {
+    name: url_accessors,
+    setup: url::Url::parse(p)?,
+    inputs: p in ["http://example.com/a?b#c", "mailto:x@example.com", "::"],
+    independent: [has_host, host_str, path, query, port_or_known_default],
+}
+
+#[derive(Debug)]
+struct Tally {
+    count: i32,
+}
+
+impl Tally {
+    fn add(&mut self, amount: i32) -> i32 {
+        self.count += amount;
+        amount
+    }
+
+    fn count(&self) -> i32 {
+        self.count
+    }
+}
+
+mr_sequence! {
+    name: tally,
+    setup: Tally { count: 0 },
+    independent: [add(1), add(2)],
+}
+
+mr_sequence! {
+    name: tally_repeated,
+    setup: Tally { count: 0 },
+    independent: [add(1), add(2), add(3)],
+}
+
+mod dependent_calls {
+    use contracts::*;
+
+    mr_sequence! {
+        #[should_panic(expected = "`count` returns 1 when called before \
+                                   `add`, but 3 when called after it")]
+        setup: super::Tally { count: 1 },
+        independent: [add(2), count],
+    }
+}
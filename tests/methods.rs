@@ -83,3 +83,245 @@ fn impl_invariant() {
     adder.prev_even();
     adder.prev_even();
 }
+
+#[derive(Debug, PartialEq)]
+struct StackModel {
+    items: Vec<i32>,
+}
+
+impl StackModel {
+    fn push(&mut self, item: i32) {
+        self.items.push(item);
+    }
+
+    fn pop(&mut self) -> Option<i32> {
+        self.items.pop()
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+}
+
+#[test]
+fn impl_model() {
+    struct Stack {
+        items: Vec<i32>,
+    }
+
+    #[model(
+        StackModel,
+        abs = |stack| StackModel { items: stack.items.clone() },
+        skip = [is_empty],
+    )]
+    impl Stack {
+        fn push(&mut self, item: i32) {
+            self.items.push(item);
+        }
+
+        fn pop(&mut self) -> Option<i32> {
+            self.items.pop()
+        }
+
+        fn len(&self) -> usize {
+            self.items.len()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.items.is_empty()
+        }
+    }
+
+    let mut stack = Stack { items: vec![] };
+    stack.push(1);
+    stack.push(2);
+    assert_eq!(stack.pop(), Some(2));
+    assert_eq!(stack.len(), 1);
+    assert!(!stack.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "model of pop violated: the model is \
+                           StackModel { items: [1] } after `pop`, but `self` \
+                           is abstracted to StackModel { items: [2] }")]
+fn impl_model_violation() {
+    struct Stack {
+        items: Vec<i32>,
+    }
+
+    #[model(StackModel, abs = |stack| StackModel { items: stack.items.clone() })]
+    impl Stack {
+        fn push(&mut self, item: i32) {
+            self.items.push(item);
+        }
+
+        fn pop(&mut self) -> Option<i32> {
+            // removes from the wrong end, but returns the right item
+            let item = self.items.last().copied();
+            if !self.items.is_empty() {
+                self.items.remove(0);
+            }
+            item
+        }
+    }
+
+    let mut stack = Stack { items: vec![] };
+    stack.push(1);
+    stack.push(2);
+    stack.pop();
+}
+
+#[test]
+fn commuting_methods() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Library {
+        available: Vec<usize>,
+        lent: Vec<usize>,
+    }
+
+    impl Library {
+        #[commutes_with(add_book, book_id + 1)]
+        #[commutes_with(lend, book_id + 1, output = self.lent.len())]
+        fn add_book(&mut self, book_id: usize) {
+            self.available.push(book_id);
+            self.available.sort_unstable();
+        }
+
+        fn lend(&mut self, book_id: usize) -> bool {
+            match self.available.iter().position(|id| *id == book_id) {
+                Some(idx) => {
+                    self.available.remove(idx);
+                    self.lent.push(book_id);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    let mut lib = Library {
+        available: vec![],
+        lent: vec![],
+    };
+    lib.add_book(1);
+    lib.add_book(3);
+    assert!(lib.lend(3));
+    lib.add_book(2);
+    assert_eq!(lib.available, vec![1, 2]);
+}
+
+#[test]
+fn mutually_commuting_methods() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Counters {
+        hits: u32,
+        misses: u32,
+    }
+
+    impl Counters {
+        #[commutes_with(miss, by)]
+        fn hit(&mut self, by: u32) {
+            self.hits += by;
+        }
+
+        #[commutes_with(hit, by)]
+        fn miss(&mut self, by: u32) {
+            self.misses += by;
+        }
+    }
+
+    let mut counters = Counters { hits: 0, misses: 0 };
+    counters.hit(2);
+    counters.miss(1);
+    assert_eq!(counters, Counters { hits: 2, misses: 1 });
+}
+
+#[test]
+#[should_panic(expected = "commutes_with of push violated: `push(item)` then \
+                           `push(item + 1)` gives [1, 2], but the other \
+                           order gives [2, 1]")]
+fn commuting_methods_violation() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Stack {
+        items: Vec<i32>,
+    }
+
+    impl Stack {
+        #[commutes_with(push, item + 1, output = self.items.clone())]
+        fn push(&mut self, item: i32) {
+            self.items.push(item);
+        }
+    }
+
+    let mut stack = Stack { items: vec![] };
+    stack.push(1);
+}
+
+#[test]
+fn concurrent_methods() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    struct Index {
+        words: Mutex<Vec<String>>,
+        lookups: AtomicUsize,
+    }
+
+    impl Index {
+        #[concurrent_consistency(threads = 8)]
+        fn position(&self, word: String, scratch: &mut Vec<usize>) -> usize {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            scratch.push(word.len());
+
+            let mut words = self.words.lock().unwrap();
+            match words.iter().position(|w| *w == word) {
+                Some(idx) => idx,
+                None => {
+                    words.push(word);
+                    words.len() - 1
+                }
+            }
+        }
+
+        #[concurrent_consistency(eq = (|a: &f64, b: &f64| (a - b).abs() < 0.5))]
+        fn load(&self) -> f64 {
+            self.lookups.load(Ordering::SeqCst) as f64 / 100.0
+        }
+    }
+
+    let index = Index {
+        words: Mutex::new(vec![]),
+        lookups: AtomicUsize::new(0),
+    };
+    let mut scratch = vec![];
+
+    assert_eq!(index.position(String::from("a"), &mut scratch), 0);
+    assert_eq!(index.position(String::from("b"), &mut scratch), 1);
+    assert_eq!(scratch, vec![1, 1]);
+    assert!(index.load() < 1.0);
+}
+
+#[test]
+#[should_panic(expected = "concurrent_consistency of next violated: `next` \
+                           returned")]
+fn concurrent_methods_violation() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Ids {
+        next: AtomicUsize,
+    }
+
+    impl Ids {
+        #[concurrent_consistency]
+        fn next(&self) -> usize {
+            self.next.fetch_add(1, Ordering::SeqCst)
+        }
+    }
+
+    let ids = Ids {
+        next: AtomicUsize::new(0),
+    };
+    ids.next();
+}
@@ -77,3 +77,172 @@ fn interpolate_example() {
     Linear::interpolate(min, max, val);
     Quadratic::interpolate(min, max, val);
 }
+
+#[test]
+fn relation_example() {
+    #[contract_trait]
+    trait Shape {
+        #[dimension_trans(scale, *, 2.0, *, 4.0)]
+        fn area(&self, label: String, scale: f64) -> f64;
+
+        #[periodicity(turns, +, 4)]
+        fn facing(self, turns: u32) -> u32;
+    }
+
+    #[derive(Clone)]
+    struct Square(f64);
+
+    #[contract_trait]
+    impl Shape for Square {
+        fn area(&self, label: String, scale: f64) -> f64 {
+            assert!(!label.is_empty());
+            self.0 * self.0 * scale * scale
+        }
+
+        fn facing(self, turns: u32) -> u32 {
+            turns % 4
+        }
+    }
+
+    let square = Square(3.0);
+
+    assert_eq!(square.area(String::from("a"), 1.0), 9.0);
+    assert_eq!(square.facing(5), 1);
+}
+
+#[test]
+#[should_panic(expected = "dimension_trans of area violated")]
+fn relation_violation() {
+    #[contract_trait]
+    trait Shape {
+        #[dimension_trans(scale, *, 2.0, *, 4.0)]
+        fn area(&self, scale: f64) -> f64;
+    }
+
+    struct Line(f64);
+
+    // scales linearly, so every call violates the relation of the trait
+    #[contract_trait]
+    impl Shape for Line {
+        fn area(&self, scale: f64) -> f64 {
+            self.0 * scale
+        }
+    }
+
+    Line(2.0).area(1.0);
+}
+
+#[test]
+fn relation_mut_example() {
+    #[contract_trait]
+    trait Wheel {
+        #[periodicity(turns, +, 4)]
+        fn turn(&mut self, turns: u32) -> u32;
+
+        #[periodicity(base, +, 10)]
+        fn digit(&self, digits: &mut Vec<u32>, base: u32) -> u32;
+    }
+
+    #[derive(Clone)]
+    struct Dial(u32);
+
+    #[contract_trait]
+    impl Wheel for Dial {
+        fn turn(&mut self, turns: u32) -> u32 {
+            self.0 = (self.0 + turns) % 4;
+            self.0
+        }
+
+        fn digit(&self, digits: &mut Vec<u32>, base: u32) -> u32 {
+            digits.sort_unstable();
+            (base + digits.iter().sum::<u32>()) % 10
+        }
+    }
+
+    let mut dial = Dial(1);
+
+    assert_eq!(dial.turn(2), 3);
+    assert_eq!(dial.0, 3);
+
+    let mut digits = vec![3, 1, 2];
+
+    assert_eq!(dial.digit(&mut digits, 1), 7);
+    assert_eq!(digits, vec![1, 2, 3]);
+}
+
+#[test]
+fn relation_non_clone_implementor() {
+    #[contract_trait]
+    trait Wheel {
+        #[periodicity(turns, +, 4)]
+        fn turn(&mut self, turns: u32) -> u32;
+
+        #[periodicity(turns, +, 4)]
+        fn facing(self, turns: u32) -> u32;
+
+        #[periodicity(base, +, 10)]
+        fn digit(&self, base: u32) -> u32;
+
+        #[periodicity(turns, +, 4)]
+        fn spin<T: Into<u32>>(&self, turns: u32, extra: T) -> u32;
+
+        fn reset(&mut self, position: u32);
+    }
+
+    // only methods whose relations clone `self` need `Clone`
+    struct Dial(u32);
+
+    #[contract_trait]
+    impl Wheel for Dial {
+        fn turn(&mut self, turns: u32) -> u32 {
+            self.0 = (self.0 + turns) % 4;
+            self.0
+        }
+
+        fn facing(self, turns: u32) -> u32 {
+            (self.0 + turns) % 4
+        }
+
+        fn digit(&self, base: u32) -> u32 {
+            (base + self.0) % 10
+        }
+
+        fn spin<T: Into<u32>>(&self, turns: u32, extra: T) -> u32 {
+            (self.0 + turns + extra.into()) % 4
+        }
+
+        fn reset(&mut self, position: u32) {
+            self.0 = position;
+        }
+    }
+
+    let mut dial = Dial(1);
+    dial.reset(3);
+
+    assert_eq!(dial.digit(4), 7);
+    assert_eq!(dial.spin(2, 1u8), 2);
+}
+
+#[test]
+#[should_panic(expected = "periodicity of digit violated")]
+fn relation_mut_violation() {
+    #[contract_trait]
+    trait Wheel {
+        #[periodicity(base, +, 10)]
+        fn digit(&self, digits: &mut Vec<u32>, base: u32) -> u32;
+    }
+
+    #[derive(Clone)]
+    struct Dial;
+
+    // forgets the modulo, so every call violates the relation of the trait
+    #[contract_trait]
+    impl Wheel for Dial {
+        fn digit(&self, digits: &mut Vec<u32>, base: u32) -> u32 {
+            digits.sort_unstable();
+            base + digits.iter().sum::<u32>()
+        }
+    }
+
+    Dial.digit(&mut vec![3, 1, 2], 1);
+}